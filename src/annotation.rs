@@ -0,0 +1,16 @@
+/// What the user wrote down about a version. The label is shown in the versions table, the note
+/// only when it is edited.
+#[derive(Clone, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Annotation {
+    pub label: String,
+    pub note: String,
+}
+
+impl Annotation {
+    pub const MAX_LABEL_LENGTH: usize = 32;
+    pub const MAX_NOTE_LENGTH: usize = 256;
+
+    pub fn is_empty(&self) -> bool {
+        self.label.is_empty() && self.note.is_empty()
+    }
+}
@@ -0,0 +1,24 @@
+use std::thread::JoinHandle;
+
+/// Work done on a separate thread, which the UI loop checks on until it has finished.
+pub struct BackgroundTask<T>(JoinHandle<Result<T, std::io::Error>>);
+
+impl<T: Send + 'static> BackgroundTask<T> {
+    pub fn start<F>(task: F) -> Self
+    where
+        F: FnOnce() -> Result<T, std::io::Error> + Send + 'static,
+    {
+        Self(std::thread::spawn(task))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
+
+    /// Waits for the result. A task that panicked fails with `ErrorKind::Other`.
+    pub fn join(self) -> Result<T, std::io::Error> {
+        self.0
+            .join()
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::Other))?
+    }
+}
@@ -0,0 +1,50 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::annotation::Annotation;
+use crate::file_op::write_atomically;
+use crate::path::{backup_index_file, index_file};
+use crate::save_format::SaveMetadata;
+use crate::schema::{FORMAT_VERSION, payload_of, with_header};
+use crate::storage::InnerType;
+use crate::version_entry::VersionKey;
+
+/// Snapshot of the index, covering every journal generation before `journal_generation`.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+pub struct Checkpoint {
+    pub journal_generation: u64,
+    pub index: InnerType,
+    pub quarantine: InnerType,
+    pub pinned: HashSet<VersionKey>,
+    pub annotations: HashMap<VersionKey, Annotation>,
+    pub undo_points: HashMap<PathBuf, SystemTime>,
+    pub metadata: HashMap<VersionKey, SaveMetadata>,
+}
+
+impl Checkpoint {
+    pub fn read(path: &Path) -> Result<Self, std::io::Error> {
+        let bytes = std::fs::read(path)?;
+
+        postcard::from_bytes(payload_of(&bytes)?)
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))
+    }
+
+    /// The checkpoint being replaced is kept as the previous generation. Should the process stop
+    /// between the two renames, only the backup remains and is picked up on the next start.
+    pub fn write(&self) -> Result<(), std::io::Error> {
+        let bytes = postcard::to_stdvec(self)
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
+
+        let index_path = index_file()?;
+        let temporary_path = index_path.with_extension("new");
+        write_atomically(&temporary_path, &with_header(FORMAT_VERSION, &bytes))?;
+
+        if index_path.exists() {
+            std::fs::rename(&index_path, backup_index_file()?)?;
+        }
+        std::fs::rename(temporary_path, index_path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,12 @@
+use fastcdc::v2020::FastCDC;
+
+const MIN_SIZE: u32 = 16 * 1024;
+const AVERAGE_SIZE: u32 = 64 * 1024;
+const MAX_SIZE: u32 = 256 * 1024;
+
+/// Splits `data` at content-defined boundaries, so an edit in one part of a save only changes the
+/// chunks around it and the rest are shared with the previous version.
+pub fn chunks(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    FastCDC::new(data, MIN_SIZE, AVERAGE_SIZE, MAX_SIZE)
+        .map(|chunk| &data[chunk.offset..chunk.offset + chunk.length])
+}
@@ -0,0 +1,32 @@
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum Codec {
+    Raw,
+    Zstd,
+}
+
+impl Codec {
+    pub const ALL: [Self; 2] = [Self::Zstd, Self::Raw];
+
+    const ZSTD_LEVEL: i32 = 3;
+
+    pub const fn extension(self) -> Option<&'static str> {
+        match self {
+            Self::Raw => None,
+            Self::Zstd => Some("zst"),
+        }
+    }
+
+    pub fn encode(self, data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+        match self {
+            Self::Raw => Ok(data.to_vec()),
+            Self::Zstd => zstd::encode_all(data, Self::ZSTD_LEVEL),
+        }
+    }
+
+    pub fn decode(self, data: Vec<u8>) -> Result<Vec<u8>, std::io::Error> {
+        match self {
+            Self::Raw => Ok(data),
+            Self::Zstd => zstd::decode_all(data.as_slice()),
+        }
+    }
+}
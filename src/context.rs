@@ -1,29 +1,122 @@
-use std::ops::{AddAssign, SubAssign};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::SystemTime;
 
+use chrono::{DateTime, Local};
 use crossterm::event;
-use crossterm::event::{Event, KeyCode};
+use crossterm::event::{Event, KeyCode, KeyModifiers};
 use ratatui::widgets::TableState;
 
+use log::LevelFilter;
+
+use crate::annotation::Annotation;
+use crate::gamestate_browser::{GamestateBrowser, GamestateLoad, load_gamestate};
+use crate::logger::{LogEntry, log_size, read_log};
+use crate::retention::RetentionPolicy;
 use crate::save_storage::SaveStorage;
 use crate::state::State;
+use crate::ui::notification::{Notification, NotificationLevel, push, take_pending};
+use crate::version_entry::VersionKey;
+
+const MAX_FILE_NAME_LENGTH: usize = 128;
+
+const MAX_NOTIFICATIONS: usize = 1000;
 
 pub struct Context {
     pub state: State,
     pub save_storage: SaveStorage,
     pub table_state: TableState,
+    pub notice: Option<String>,
+    pub text_input: String,
+    pub selection: HashSet<SystemTime>,
+    selection_anchor: Option<usize>,
+    pub corrupted_versions: Vec<VersionKey>,
+    pub notifications: Vec<Notification>,
+    pub log_entries: Vec<LogEntry>,
+    pub log_filter: LevelFilter,
+    log_size: Option<u64>,
+    pub gamestate_browser: Option<GamestateBrowser>,
+    gamestate_load: Option<GamestateLoad>,
 }
 
 impl Context {
-    pub fn new() -> Self {
-        Self {
+    pub fn new() -> Result<Self, std::io::Error> {
+        let retention = RetentionPolicy::read().unwrap_or_else(|e| {
+            push(
+                NotificationLevel::Warning,
+                format!("Retention rules were not read, every version is kept: {e}"),
+            );
+            RetentionPolicy::default()
+        });
+        let mut save_storage = SaveStorage::new(retention)?;
+        let notice = save_storage.take_recovery().map(|r| r.to_string());
+
+        Ok(Self {
             state: State::MainMenu(0, false),
-            save_storage: SaveStorage::new(),
+            save_storage,
             table_state: TableState::default(),
-        }
+            notice,
+            text_input: String::new(),
+            selection: HashSet::new(),
+            selection_anchor: None,
+            corrupted_versions: Vec::new(),
+            notifications: Vec::new(),
+            log_entries: Vec::new(),
+            log_filter: LevelFilter::Info,
+            log_size: None,
+            gamestate_browser: None,
+            gamestate_load: None,
+        })
     }
 
     pub fn update(&mut self) {
         self.save_storage.update();
+
+        match self.save_storage.take_verification_result() {
+            None => {}
+            Some(Ok(corrupted_versions)) if corrupted_versions.is_empty() => {
+                push(NotificationLevel::Info, "All versions passed verification");
+            }
+            Some(Ok(corrupted_versions)) => {
+                self.corrupted_versions = corrupted_versions;
+                if let State::MainMenu(_, false) = self.state {
+                    self.state = State::CorruptedVersions(0);
+                } else {
+                    // Whatever is open on screen is not dropped for it.
+                    push(
+                        NotificationLevel::Warning,
+                        format!(
+                            "Verification found {} corrupted versions, press [C] in the file list to review them",
+                            self.corrupted_versions.len()
+                        ),
+                    );
+                }
+            }
+            Some(Err(e)) => {
+                push(
+                    NotificationLevel::Error,
+                    format!("Verification failed: {e}"),
+                );
+            }
+        }
+
+        if let State::Logs(_) = self.state {
+            self.refresh_log();
+        }
+
+        if self
+            .gamestate_load
+            .as_ref()
+            .is_some_and(GamestateLoad::is_finished)
+        {
+            self.finish_loading_gamestate();
+        }
+
+        self.notifications.extend(take_pending());
+        if self.notifications.len() > MAX_NOTIFICATIONS {
+            let excess = self.notifications.len() - MAX_NOTIFICATIONS;
+            self.notifications.drain(..excess);
+        }
     }
 
     pub fn should_exit(&self) -> bool {
@@ -43,19 +136,93 @@ impl Context {
             return Ok(());
         }
 
+        if self.notice.is_some() {
+            if let KeyCode::Enter | KeyCode::Esc = key.code {
+                self.notice = None;
+            }
+
+            return Ok(());
+        }
+
+        if let State::EditLabel(..) | State::EditNote(..) | State::RestoreAs(..) = self.state {
+            self.edit_text(key.code);
+
+            return Ok(());
+        }
+
         match key.code {
+            KeyCode::Up if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.extend_selection(true);
+            }
+            KeyCode::Down if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.extend_selection(false);
+            }
             KeyCode::Up => {
                 self.cursor_up();
             }
             KeyCode::Down => {
                 self.cursor_down();
             }
+            KeyCode::PageUp => {
+                self.page_up();
+            }
+            KeyCode::PageDown => {
+                self.page_down();
+            }
+            KeyCode::Left => {
+                self.collapse();
+            }
+            KeyCode::Right => {
+                self.expand();
+            }
             KeyCode::Enter => {
                 self.enter();
             }
             KeyCode::Esc => {
                 self.exit();
             }
+            KeyCode::Char('v') => {
+                self.verify();
+            }
+            KeyCode::Char('p') => {
+                self.toggle_pin();
+            }
+            KeyCode::Char('l') if matches!(self.state, State::MainMenu(_, false)) => {
+                self.show_logs();
+            }
+            KeyCode::Char('l') => {
+                self.start_editing(false);
+            }
+            KeyCode::Char('f') => {
+                self.cycle_log_filter();
+            }
+            KeyCode::Char('n') => {
+                self.start_editing(true);
+            }
+            KeyCode::Char('d') => {
+                self.delete();
+            }
+            KeyCode::Char('e') => {
+                self.export();
+            }
+            KeyCode::Char('a') => {
+                self.start_restoring_as();
+            }
+            KeyCode::Char('u') => {
+                self.undo_restore();
+            }
+            KeyCode::Char('m') => {
+                self.show_notifications();
+            }
+            KeyCode::Char('c') => {
+                self.show_corrupted_versions();
+            }
+            KeyCode::Char('b') => {
+                self.browse_gamestate();
+            }
+            KeyCode::Char(' ') => {
+                self.toggle_selection();
+            }
             _ => return Ok(()),
         };
 
@@ -64,55 +231,586 @@ impl Context {
 
     pub fn cursor_up(&mut self) {
         match &mut self.state {
-            State::MainMenu(index, false) | State::SaveFileSelected(index, _, false) => {
-                index.sub_assign(1);
+            State::MainMenu(index, false)
+            | State::SaveFileSelected(index, _)
+            | State::CorruptedVersions(index)
+            | State::Notifications(index)
+            | State::Logs(index)
+            | State::Gamestate(index, ..) => {
+                *index = index.saturating_sub(1);
             }
             _ => {}
         }
     }
 
     pub fn cursor_down(&mut self) {
+        let row_count = self.row_count();
         match &mut self.state {
-            State::MainMenu(index, false) | State::SaveFileSelected(index, _, false) => {
-                index.add_assign(1);
+            State::MainMenu(index, false)
+            | State::SaveFileSelected(index, _)
+            | State::CorruptedVersions(index)
+            | State::Notifications(index)
+            | State::Logs(index)
+            | State::Gamestate(index, ..)
+                if *index + 1 < row_count =>
+            {
+                *index += 1;
             }
             _ => {}
         }
     }
 
+    /// Keeps the cursor on a file whose versions changed, since that may move it in the list.
+    fn follow_file(&mut self, path: &Path) {
+        let position = self
+            .save_storage
+            .save_files()
+            .position(|f| f.path() == path);
+        self.state = match (self.state.clone(), position) {
+            (State::MainMenu(_, false), Some(main_menu_index)) => {
+                State::MainMenu(main_menu_index, false)
+            }
+            (State::SaveFileSelected(index, _), Some(main_menu_index)) => {
+                State::SaveFileSelected(index, main_menu_index)
+            }
+            (State::SaveFileSelected(_, main_menu_index), None) => {
+                State::MainMenu(main_menu_index, false)
+            }
+            (state, _) => state,
+        };
+        self.clamp_cursor();
+    }
+
+    /// Moves the cursor back onto the last row when the rows under it are gone.
+    fn clamp_cursor(&mut self) {
+        let last_row = self.row_count().saturating_sub(1);
+        match &mut self.state {
+            State::MainMenu(index, _)
+            | State::SaveFileSelected(index, _)
+            | State::Restore(index, ..)
+            | State::CorruptedVersions(index)
+            | State::Logs(index)
+            | State::Gamestate(index, ..) => {
+                *index = (*index).min(last_row);
+            }
+            _ => {}
+        }
+    }
+
+    fn row_count(&self) -> usize {
+        match self.state {
+            State::MainMenu(..) | State::DeleteFile(..) => self.save_storage.save_files().count(),
+            State::SaveFileSelected(_, main_menu_index)
+            | State::Restore(_, main_menu_index, _)
+            | State::DeleteVersion(_, main_menu_index, _)
+            | State::EditLabel(_, main_menu_index, _)
+            | State::EditNote(_, main_menu_index, _)
+            | State::RestoreAs(_, main_menu_index, _) => self
+                .save_storage
+                .save_files()
+                .nth(main_menu_index)
+                .map_or(0, |f| self.save_storage.save_versions(f.path()).count()),
+            State::CorruptedVersions(_) => self.corrupted_versions.len(),
+            State::Notifications(_) => self.notifications.len(),
+            State::Logs(_) => self.log_entries.len(),
+            State::Gamestate(..) => self
+                .gamestate_browser
+                .as_ref()
+                .map_or(0, GamestateBrowser::len),
+            State::Exit => 0,
+        }
+    }
+
     pub fn enter(&mut self) {
         match self.state {
-            State::Exit => {}
+            State::Exit | State::Notifications(_) | State::Logs(_) => {}
             State::MainMenu(index, false) => {
-                self.state = State::SaveFileSelected(0, index, false);
+                self.clear_selection();
+                self.state = State::SaveFileSelected(0, index);
             }
             State::MainMenu(_, true) => {
                 self.state = State::Exit;
             }
-            State::SaveFileSelected(index, main_menu_index, false) => {
-                self.state = State::SaveFileSelected(index, main_menu_index, true);
-            }
-            State::SaveFileSelected(index, main_menu_index, true) => {
-                let Some(save_file) = self.save_storage.save_files().nth(main_menu_index) else {
+            State::SaveFileSelected(index, main_menu_index) => {
+                let Some(version) = self.version_at(index, main_menu_index) else {
                     return;
                 };
 
-                let path = save_file.path();
-                let Some(version) = self.save_storage.save_versions(path).nth(index) else {
+                self.state = State::Restore(index, main_menu_index, version);
+            }
+            State::Restore(index, main_menu_index, ref version) => {
+                let (path, time) = version.clone();
+                self.state = State::SaveFileSelected(index, main_menu_index);
+
+                match self.save_storage.restore(&path, &time) {
+                    Ok(()) => push(NotificationLevel::Info, "Version was restored and verified"),
+                    Err(e) => push(
+                        NotificationLevel::Error,
+                        format!("Version was not restored: {e}"),
+                    ),
+                }
+                self.follow_file(&path);
+            }
+            State::EditLabel(index, main_menu_index, ref version)
+            | State::EditNote(index, main_menu_index, ref version) => {
+                let is_label = matches!(self.state, State::EditLabel(..));
+                let (path, time) = version.clone();
+                self.state = State::SaveFileSelected(index, main_menu_index);
+
+                let text = std::mem::take(&mut self.text_input).trim().to_owned();
+
+                let mut annotation = self.save_storage.annotation_of(&path, &time);
+                if is_label {
+                    annotation.label = text;
+                } else {
+                    annotation.note = text;
+                }
+
+                if let Err(e) = self.save_storage.annotate(&path, &time, annotation) {
+                    push(
+                        NotificationLevel::Error,
+                        format!("Annotation was not saved: {e}"),
+                    );
+                }
+            }
+            State::DeleteFile(index, ref path) => {
+                let path = path.clone();
+                self.state = State::MainMenu(index, false);
+
+                let versions = self
+                    .save_storage
+                    .save_versions(&path)
+                    .map(|version| (path.clone(), *version.time()))
+                    .collect();
+                self.delete_versions(versions);
+                self.clamp_cursor();
+            }
+            State::DeleteVersion(index, main_menu_index, ref mut versions) => {
+                let versions = std::mem::take(versions);
+                self.state = State::SaveFileSelected(index, main_menu_index);
+
+                let Some((path, _)) = versions.first().cloned() else {
                     return;
                 };
-                let Some(data) = self.save_storage.data_of(path, version.time()) else {
+
+                self.delete_versions(versions);
+                self.clear_selection();
+                self.follow_file(&path);
+            }
+            State::RestoreAs(index, main_menu_index, ref version) => {
+                let (path, time) = version.clone();
+                self.state = State::SaveFileSelected(index, main_menu_index);
+
+                let file_name = std::mem::take(&mut self.text_input);
+
+                match self.save_storage.restore_as(&path, &time, file_name.trim()) {
+                    Ok(new_path) => push(
+                        NotificationLevel::Info,
+                        format!("Version was restored to {}", new_path.display()),
+                    ),
+                    Err(e) => push(
+                        NotificationLevel::Error,
+                        format!("Version was not restored: {e}"),
+                    ),
+                }
+            }
+            State::Gamestate(cursor, ..) => {
+                if let Some(gamestate_browser) = &mut self.gamestate_browser {
+                    gamestate_browser.toggle(cursor);
+                }
+            }
+            State::CorruptedVersions(_) => {
+                let corrupted_versions = std::mem::take(&mut self.corrupted_versions);
+                match self.save_storage.quarantine(&corrupted_versions) {
+                    Ok(()) => push(
+                        NotificationLevel::Info,
+                        format!("Quarantined {} versions", corrupted_versions.len()),
+                    ),
+                    Err(e) => push(NotificationLevel::Error, format!("Quarantine failed: {e}")),
+                }
+
+                self.state = State::MainMenu(0, false);
+            }
+        }
+    }
+
+    pub fn show_notifications(&mut self) {
+        if let State::MainMenu(_, false) = self.state {
+            self.state = State::Notifications(0);
+        }
+    }
+
+    /// Opens the result of a verification that finished while something else was on screen.
+    pub fn show_corrupted_versions(&mut self) {
+        if matches!(self.state, State::MainMenu(_, false)) && !self.corrupted_versions.is_empty() {
+            self.state = State::CorruptedVersions(0);
+        }
+    }
+
+    pub fn show_logs(&mut self) {
+        if let State::MainMenu(_, false) = self.state {
+            self.log_size = None;
+            self.state = State::Logs(0);
+        }
+    }
+
+    /// Steps through the levels the log is written at, from errors only to everything.
+    pub fn cycle_log_filter(&mut self) {
+        if let State::Logs(_) = self.state {
+            self.log_filter = match self.log_filter {
+                LevelFilter::Error => LevelFilter::Warn,
+                LevelFilter::Warn => LevelFilter::Info,
+                LevelFilter::Info => LevelFilter::Debug,
+                _ => LevelFilter::Error,
+            };
+            self.log_size = None;
+            self.state = State::Logs(0);
+        }
+    }
+
+    /// Reads the log again only when it has changed since the last read.
+    fn refresh_log(&mut self) {
+        let size = log_size().ok();
+        if size.is_some() && size == self.log_size {
+            return;
+        }
+
+        self.log_size = size;
+        self.log_entries = read_log(self.log_filter).unwrap_or_default();
+        self.clamp_cursor();
+    }
+
+    /// Reads the version under the cursor and starts parsing its gamestate, which is shown once
+    /// it is done.
+    pub fn browse_gamestate(&mut self) {
+        let State::SaveFileSelected(index, main_menu_index) = self.state else {
+            return;
+        };
+
+        let Some((path, time)) = self.version_at(index, main_menu_index) else {
+            return;
+        };
+
+        match self.save_storage.data_of(&path, &time) {
+            Ok(data) => {
+                self.gamestate_browser = None;
+                self.gamestate_load = Some(load_gamestate(data));
+                self.state = State::Gamestate(0, index, main_menu_index);
+            }
+            Err(e) => push(
+                NotificationLevel::Error,
+                format!("Version could not be read: {e}"),
+            ),
+        }
+    }
+
+    pub const fn is_loading_gamestate(&self) -> bool {
+        self.gamestate_load.is_some()
+    }
+
+    fn finish_loading_gamestate(&mut self) {
+        let Some(gamestate_load) = self.gamestate_load.take() else {
+            return;
+        };
+
+        match gamestate_load.join() {
+            Ok(gamestate_browser) => self.gamestate_browser = Some(gamestate_browser),
+            Err(e) => {
+                push(
+                    NotificationLevel::Error,
+                    format!("Gamestate could not be parsed: {e}"),
+                );
+                self.exit();
+            }
+        }
+    }
+
+    pub fn page_up(&mut self) {
+        let Some(gamestate_browser) = &self.gamestate_browser else {
+            return;
+        };
+
+        let page_size = gamestate_browser.page_size();
+        if let State::Gamestate(cursor, ..) = &mut self.state {
+            *cursor = cursor.saturating_sub(page_size);
+        }
+    }
+
+    pub fn page_down(&mut self) {
+        let Some(gamestate_browser) = &self.gamestate_browser else {
+            return;
+        };
+
+        let page_size = gamestate_browser.page_size();
+        let last_row = gamestate_browser.len().saturating_sub(1);
+        if let State::Gamestate(cursor, ..) = &mut self.state {
+            *cursor = (*cursor + page_size).min(last_row);
+        }
+    }
+
+    pub fn expand(&mut self) {
+        if let (State::Gamestate(cursor, ..), Some(gamestate_browser)) =
+            (&self.state, &mut self.gamestate_browser)
+        {
+            gamestate_browser.expand(*cursor);
+        }
+    }
+
+    /// Collapses the block under the cursor, or moves the cursor to the block holding it.
+    pub fn collapse(&mut self) {
+        if let (State::Gamestate(cursor, ..), Some(gamestate_browser)) =
+            (&mut self.state, &mut self.gamestate_browser)
+        {
+            *cursor = gamestate_browser.collapse(*cursor);
+        }
+    }
+
+    pub fn undo_restore(&mut self) {
+        let (State::MainMenu(main_menu_index, false) | State::SaveFileSelected(_, main_menu_index)) =
+            self.state
+        else {
+            return;
+        };
+
+        let Some(save_file) = self.save_storage.save_files().nth(main_menu_index) else {
+            return;
+        };
+
+        let path = save_file.path().clone();
+        match self.save_storage.undo_restore(&path) {
+            Ok(()) => push(NotificationLevel::Info, "Last restore was undone"),
+            Err(e) => push(
+                NotificationLevel::Error,
+                format!("Restore was not undone: {e}"),
+            ),
+        }
+        self.follow_file(&path);
+    }
+
+    /// Asks for confirmation. What is to be deleted is settled here, since versions captured while
+    /// the popup is open move the rows under the cursor.
+    pub fn delete(&mut self) {
+        match self.state {
+            State::MainMenu(index, false) => {
+                let Some(save_file) = self.save_storage.save_files().nth(index) else {
                     return;
                 };
 
-                let data = data.to_vec();
+                self.state = State::DeleteFile(index, save_file.path().clone());
+            }
+            State::SaveFileSelected(index, main_menu_index) => {
+                let versions = self.targeted_versions(index, main_menu_index);
+                if versions.is_empty() {
+                    return;
+                }
+
+                self.state = State::DeleteVersion(index, main_menu_index, versions);
+            }
+            _ => {}
+        }
+    }
+
+    fn delete_versions(&mut self, versions: Vec<VersionKey>) {
+        let count = versions.len();
+        match self.save_storage.delete(versions) {
+            Ok(deleted) if deleted < count => {
+                push(
+                    NotificationLevel::Warning,
+                    format!("Kept {} pinned versions", count - deleted),
+                );
+            }
+            Ok(deleted) => push(
+                NotificationLevel::Info,
+                format!("Deleted {deleted} versions"),
+            ),
+            Err(e) => push(
+                NotificationLevel::Error,
+                format!("Versions were not deleted: {e}"),
+            ),
+        }
+    }
+
+    /// Pins the targeted versions unless all of them are pinned already, in which case they are
+    /// unpinned.
+    pub fn toggle_pin(&mut self) {
+        let State::SaveFileSelected(index, main_menu_index) = self.state else {
+            return;
+        };
+
+        let versions = self.targeted_versions(index, main_menu_index);
+        let is_pinned = versions
+            .iter()
+            .all(|(path, time)| self.save_storage.is_pinned(path, time));
+
+        if let Err(e) = self.save_storage.set_pinned(&versions, !is_pinned) {
+            push(
+                NotificationLevel::Error,
+                format!("Pin was not changed: {e}"),
+            );
+        }
+    }
+
+    pub fn export(&mut self) {
+        let State::SaveFileSelected(index, main_menu_index) = self.state else {
+            return;
+        };
+
+        let versions = self.targeted_versions(index, main_menu_index);
+        let count = versions.len();
+        match self.save_storage.export(&versions) {
+            Ok(directory) => push(
+                NotificationLevel::Info,
+                format!("Exported {count} versions to {}", directory.display()),
+            ),
+            Err(e) => push(
+                NotificationLevel::Error,
+                format!("Versions were not exported: {e}"),
+            ),
+        }
+    }
+
+    pub fn toggle_selection(&mut self) {
+        let State::SaveFileSelected(index, main_menu_index) = self.state else {
+            return;
+        };
+
+        let Some((_, time)) = self.version_at(index, main_menu_index) else {
+            return;
+        };
 
-                self.save_storage.add_ignore_record(path.clone());
+        if !self.selection.remove(&time) {
+            self.selection.insert(time);
+        }
+        self.selection_anchor = Some(index);
+    }
 
-                let _ = std::fs::write(path, data);
+    /// Moves the cursor and selects every row between it and the row the selection started from.
+    pub fn extend_selection(&mut self, is_up: bool) {
+        let State::SaveFileSelected(index, main_menu_index) = self.state else {
+            return;
+        };
 
-                self.state = State::SaveFileSelected(index, main_menu_index, false);
+        let anchor = *self.selection_anchor.get_or_insert(index);
+        if is_up {
+            self.cursor_up();
+        } else {
+            self.cursor_down();
+        }
+
+        let State::SaveFileSelected(cursor, _) = self.state else {
+            return;
+        };
+        let Some(save_file) = self.save_storage.save_files().nth(main_menu_index) else {
+            return;
+        };
+
+        let first = anchor.min(cursor);
+        let last = anchor.max(cursor);
+        self.selection.extend(
+            self.save_storage
+                .save_versions(save_file.path())
+                .skip(first)
+                .take(last - first + 1)
+                .map(|version| *version.time()),
+        );
+    }
+
+    fn clear_selection(&mut self) {
+        self.selection.clear();
+        self.selection_anchor = None;
+    }
+
+    /// The selected versions, or the one under the cursor when none are selected.
+    fn targeted_versions(&self, index: usize, main_menu_index: usize) -> Vec<VersionKey> {
+        if self.selection.is_empty() {
+            return self
+                .version_at(index, main_menu_index)
+                .into_iter()
+                .collect();
+        }
+
+        let Some(save_file) = self.save_storage.save_files().nth(main_menu_index) else {
+            return Vec::new();
+        };
+
+        let path = save_file.path();
+        self.save_storage
+            .save_versions(path)
+            .filter(|version| self.selection.contains(version.time()))
+            .map(|version| (path.clone(), *version.time()))
+            .collect()
+    }
+
+    /// Opens the text input on the label or the note of the version under the cursor, starting
+    /// from what it currently says. The version is kept, as the rows may move while typing.
+    pub fn start_editing(&mut self, is_note: bool) {
+        let State::SaveFileSelected(index, main_menu_index) = self.state else {
+            return;
+        };
+
+        let Some((path, time)) = self.version_at(index, main_menu_index) else {
+            return;
+        };
+
+        let annotation = self.save_storage.annotation_of(&path, &time);
+        if is_note {
+            self.text_input = annotation.note;
+            self.state = State::EditNote(index, main_menu_index, (path, time));
+        } else {
+            self.text_input = annotation.label;
+            self.state = State::EditLabel(index, main_menu_index, (path, time));
+        }
+    }
+
+    /// Opens the text input on a new file name for the version under the cursor, suggesting the
+    /// name of its file followed by the time it was captured. The version is kept, as the rows may
+    /// move while typing.
+    pub fn start_restoring_as(&mut self) {
+        let State::SaveFileSelected(index, main_menu_index) = self.state else {
+            return;
+        };
+
+        let Some((path, time)) = self.version_at(index, main_menu_index) else {
+            return;
+        };
+
+        let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let time_string = DateTime::<Local>::from(time).format("%Y-%m-%d_%H-%M-%S");
+        self.text_input = format!("{file_stem}_{time_string}.ck3");
+        self.state = State::RestoreAs(index, main_menu_index, (path, time));
+    }
+
+    fn edit_text(&mut self, key_code: KeyCode) {
+        let max_length = match self.state {
+            State::EditLabel(..) => Annotation::MAX_LABEL_LENGTH,
+            State::EditNote(..) => Annotation::MAX_NOTE_LENGTH,
+            _ => MAX_FILE_NAME_LENGTH,
+        };
+
+        match key_code {
+            KeyCode::Char(c) if self.text_input.chars().count() < max_length => {
+                self.text_input.push(c);
+            }
+            KeyCode::Backspace => {
+                self.text_input.pop();
             }
+            KeyCode::Enter => self.enter(),
+            KeyCode::Esc => self.exit(),
+            _ => {}
+        }
+    }
+
+    fn version_at(&self, index: usize, main_menu_index: usize) -> Option<VersionKey> {
+        let save_file = self.save_storage.save_files().nth(main_menu_index)?;
+        let path = save_file.path();
+        let version = self.save_storage.save_versions(path).nth(index)?;
+
+        Some((path.clone(), *version.time()))
+    }
+
+    pub fn verify(&mut self) {
+        if let State::MainMenu(_, false) = self.state {
+            self.save_storage.start_verification();
         }
     }
 
@@ -121,11 +819,32 @@ impl Context {
             State::Exit => {}
             State::MainMenu(index, false) => self.state = State::MainMenu(index, true),
             State::MainMenu(index, true) => self.state = State::MainMenu(index, false),
-            State::SaveFileSelected(_, main_menu_index, false) => {
+            State::SaveFileSelected(_, main_menu_index) => {
+                self.clear_selection();
                 self.state = State::MainMenu(main_menu_index, false);
             }
-            State::SaveFileSelected(index, main_menu_index, true) => {
-                self.state = State::SaveFileSelected(index, main_menu_index, false);
+            State::Restore(index, main_menu_index, _) => {
+                self.state = State::SaveFileSelected(index, main_menu_index);
+            }
+            State::DeleteFile(index, _) => self.state = State::MainMenu(index, false),
+            State::DeleteVersion(index, main_menu_index, _) => {
+                self.state = State::SaveFileSelected(index, main_menu_index);
+            }
+            State::EditLabel(index, main_menu_index, _)
+            | State::EditNote(index, main_menu_index, _)
+            | State::RestoreAs(index, main_menu_index, _) => {
+                self.text_input.clear();
+                self.state = State::SaveFileSelected(index, main_menu_index);
+            }
+            State::CorruptedVersions(_) => {
+                self.corrupted_versions.clear();
+                self.state = State::MainMenu(0, false);
+            }
+            State::Notifications(_) | State::Logs(_) => self.state = State::MainMenu(0, false),
+            State::Gamestate(_, index, main_menu_index) => {
+                self.gamestate_browser = None;
+                self.gamestate_load = None;
+                self.state = State::SaveFileSelected(index, main_menu_index);
             }
         }
     }
@@ -1,10 +1,92 @@
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+use crate::object_hash::ObjectHash;
 use crate::save_file_watcher::SaveFileUpdate;
 
+const TEMPORARY_EXTENSION: &str = "tmp";
+
 pub fn gather_file_data(path: &PathBuf) -> Result<SaveFileUpdate, std::io::Error> {
     let modified = std::fs::metadata(path)?.modified()?;
     let save_data = std::fs::read(path)?;
 
     Ok((path.clone(), modified, save_data))
 }
+
+/// Writes through a temporary file in the same directory which is synced and renamed over `path`,
+/// so a crash leaves either the old or the new contents and never a partial file.
+pub fn write_atomically(path: &Path, data: &[u8]) -> Result<(), std::io::Error> {
+    let temporary_path = write_temporary(path, data)?;
+
+    std::fs::rename(temporary_path, path)?;
+    sync_parent_directory(path)
+}
+
+/// Like `write_atomically`, but the temporary file is linked into place, which fails with
+/// `AlreadyExists` instead of replacing a file at `path`.
+fn write_new_atomically(path: &Path, data: &[u8]) -> Result<(), std::io::Error> {
+    let temporary_path = write_temporary(path, data)?;
+
+    let linked = std::fs::hard_link(&temporary_path, path);
+    std::fs::remove_file(temporary_path)?;
+    linked?;
+
+    sync_parent_directory(path)
+}
+
+/// Writes atomically, then reads the file back and compares it against `data`. Returns the
+/// modification time of the written file.
+pub fn write_verified(path: &Path, data: &[u8]) -> Result<SystemTime, std::io::Error> {
+    write_atomically(path, data)?;
+
+    verify(path, data)
+}
+
+/// As `write_verified`, never replacing an existing file.
+pub fn write_new_verified(path: &Path, data: &[u8]) -> Result<SystemTime, std::io::Error> {
+    write_new_atomically(path, data)?;
+
+    verify(path, data)
+}
+
+fn write_temporary(path: &Path, data: &[u8]) -> Result<PathBuf, std::io::Error> {
+    let temporary_path = path.with_extension(TEMPORARY_EXTENSION);
+
+    let mut file = std::fs::File::create(&temporary_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+
+    Ok(temporary_path)
+}
+
+/// Reads the file back and compares it against `data`. Returns its modification time.
+fn verify(path: &Path, data: &[u8]) -> Result<SystemTime, std::io::Error> {
+    if ObjectHash::of(&std::fs::read(path)?) != ObjectHash::of(data) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} does not match what was written", path.display()),
+        ));
+    }
+
+    std::fs::metadata(path)?.modified()
+}
+
+/// Files left behind by `write_atomically`, which are not save files.
+pub fn is_temporary(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == TEMPORARY_EXTENSION)
+}
+
+#[cfg(unix)]
+fn sync_parent_directory(path: &Path) -> Result<(), std::io::Error> {
+    match path.parent() {
+        Some(parent) => std::fs::File::open(parent)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_directory(_path: &Path) -> Result<(), std::io::Error> {
+    Ok(())
+}
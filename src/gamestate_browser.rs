@@ -0,0 +1,160 @@
+use crate::background_task::BackgroundTask;
+use crate::save_format::parse_gamestate;
+use crate::save_format::tree::{Entry, Tree};
+
+/// An entry shown on screen, `depth` levels below the top of the gamestate.
+struct Row {
+    index: usize,
+    depth: usize,
+    /// Entries or items of a block, `None` for a scalar.
+    child_count: Option<usize>,
+    is_expanded: bool,
+}
+
+/// The gamestate of a version as a list of rows that grows as blocks are expanded. Only the rows
+/// of expanded blocks exist, and only the ones on screen are turned into text.
+pub struct GamestateBrowser {
+    tree: Tree<'static>,
+    rows: Vec<Row>,
+    /// First row on screen. Kept even so that the row stripes do not flicker while scrolling.
+    offset: usize,
+    page_size: usize,
+}
+
+impl GamestateBrowser {
+    pub fn new(tree: Tree<'static>) -> Self {
+        let rows = rows_of(tree.root(), 0).collect();
+
+        Self {
+            tree,
+            rows,
+            offset: 0,
+            page_size: 1,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Rows on screen when it was last drawn.
+    pub const fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    pub fn toggle(&mut self, cursor: usize) {
+        match self.rows.get(cursor) {
+            Some(row) if row.is_expanded => {
+                self.collapse(cursor);
+            }
+            Some(_) => self.expand(cursor),
+            None => {}
+        }
+    }
+
+    pub fn expand(&mut self, cursor: usize) {
+        let Some(row) = self.rows.get_mut(cursor) else {
+            return;
+        };
+        if row.is_expanded || row.child_count.is_none() {
+            return;
+        }
+        row.is_expanded = true;
+        let (index, depth) = (row.index, row.depth);
+
+        let children = rows_of(self.tree.entry(index), depth + 1);
+        self.rows.splice(cursor + 1..cursor + 1, children);
+    }
+
+    /// Collapses the block under the cursor, or moves to the block holding it when there is
+    /// nothing to collapse. Returns where the cursor ends up.
+    pub fn collapse(&mut self, cursor: usize) -> usize {
+        let Some(row) = self.rows.get_mut(cursor) else {
+            return cursor;
+        };
+        let depth = row.depth;
+
+        if row.is_expanded {
+            row.is_expanded = false;
+            let end = self.rows[cursor + 1..]
+                .iter()
+                .position(|row| row.depth <= depth)
+                .map_or(self.rows.len(), |position| cursor + 1 + position);
+            self.rows.drain(cursor + 1..end);
+
+            return cursor;
+        }
+
+        self.rows[..cursor]
+            .iter()
+            .rposition(|row| row.depth < depth)
+            .unwrap_or(cursor)
+    }
+
+    /// Scrolls just enough to keep the cursor among the `height` rows on screen, and returns the
+    /// key and value of each of them together with the cursor position on screen.
+    pub fn window(&mut self, cursor: usize, height: usize) -> (Vec<[String; 2]>, usize) {
+        let height = height.max(1);
+        self.page_size = height;
+
+        if cursor < self.offset {
+            self.offset = cursor - cursor % 2;
+        } else if cursor >= self.offset + height {
+            self.offset = (cursor + 1 - height).next_multiple_of(2);
+        }
+        // Only a screen a single row high can lose the cursor to the rounding.
+        self.offset = self.offset.min(cursor);
+
+        let rows = self
+            .rows
+            .iter()
+            .skip(self.offset)
+            .take(height)
+            .map(|row| self.texts_of(row))
+            .collect();
+
+        (rows, cursor - self.offset)
+    }
+
+    fn texts_of(&self, row: &Row) -> [String; 2] {
+        let entry = self.tree.entry(row.index);
+
+        let marker = match (row.child_count, row.is_expanded) {
+            (None, _) => "  ",
+            (Some(_), false) => "▸ ",
+            (Some(_), true) => "▾ ",
+        };
+        let key = entry
+            .key()
+            .map(|key| key.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let key = format!("{}{marker}{key}", "  ".repeat(row.depth));
+
+        let value = match (entry.scalar(), row.child_count) {
+            (Some(scalar), _) => scalar.to_string_lossy().into_owned(),
+            (None, Some(count)) if !row.is_expanded && entry.is_array() => {
+                format!("[ {count} items ]")
+            }
+            (None, Some(count)) if !row.is_expanded => format!("{{ {count} entries }}"),
+            (None, _) => String::new(),
+        };
+
+        [key, value]
+    }
+}
+
+fn rows_of(entry: Entry<'_>, depth: usize) -> impl Iterator<Item = Row> + '_ {
+    entry.children().map(move |child| Row {
+        index: child.index(),
+        depth,
+        child_count: child.scalar().is_none().then(|| child.children().count()),
+        is_expanded: false,
+    })
+}
+
+pub type GamestateLoad = BackgroundTask<GamestateBrowser>;
+
+/// Parses the gamestate of a version on a separate thread, since a large save takes a moment.
+pub fn load_gamestate(data: Vec<u8>) -> GamestateLoad {
+    BackgroundTask::start(move || parse_gamestate(data).map(GamestateBrowser::new))
+}
@@ -0,0 +1,284 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::annotation::Annotation;
+use crate::path::{damaged_journal_file, journal_file, save_data};
+use crate::save_format::SaveMetadata;
+use crate::schema::{FORMAT_VERSION, HEADER_SIZE, header, payload_of};
+use crate::version_entry::VersionEntry;
+
+const RECORD_HEADER_SIZE: usize = 8;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub enum JournalRecord {
+    Add(PathBuf, SystemTime, VersionEntry),
+    Quarantine(PathBuf, SystemTime),
+    Remove(PathBuf, SystemTime),
+    Pin(PathBuf, SystemTime, bool),
+    Annotate(PathBuf, SystemTime, Annotation),
+    UndoPoint(PathBuf, Option<SystemTime>),
+    Describe(PathBuf, SystemTime, SaveMetadata),
+}
+
+/// Records read back from one journal generation.
+pub struct Replay {
+    pub records: Vec<JournalRecord>,
+    /// Whether damaged records were skipped or cut off. The file is copied aside as it was
+    /// before that.
+    pub is_damaged: bool,
+}
+
+/// Append-only log of changes made since the last checkpoint. Each record is framed with its
+/// length and CRC, so a record torn by a crash is detected and cut off on the next start.
+pub struct Journal {
+    generation: u64,
+    file: File,
+    records: usize,
+}
+
+impl Journal {
+    /// `records` is how many records the generations since the last checkpoint already hold, so
+    /// that the ones replayed at start count towards compaction too.
+    pub fn open(generation: u64, records: usize) -> Result<Self, std::io::Error> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(journal_file(generation)?)?;
+
+        if file.metadata()?.len() == 0 {
+            file.write_all(&header(FORMAT_VERSION))?;
+            file.sync_data()?;
+        }
+
+        Ok(Self {
+            generation,
+            file,
+            records,
+        })
+    }
+
+    pub const fn len(&self) -> usize {
+        self.records
+    }
+
+    pub fn append(&mut self, record: &JournalRecord) -> Result<(), std::io::Error> {
+        let frame = Self::encode_frame(record)?;
+
+        self.file.write_all(&frame)?;
+        self.file.sync_data()?;
+        self.records += 1;
+
+        Ok(())
+    }
+
+    /// Continues in a new journal file and returns its generation. Records in earlier
+    /// generations are covered by the checkpoint written for the new one.
+    pub fn rotate(&mut self) -> Result<u64, std::io::Error> {
+        *self = Self::open(self.generation + 1, 0)?;
+
+        Ok(self.generation)
+    }
+
+    /// Existing journal generations starting from `first`, in ascending order.
+    pub fn generations_from(first: u64) -> Result<Vec<u64>, std::io::Error> {
+        let entries = match std::fs::read_dir(save_data()?) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut generations = entries
+            .flatten()
+            .filter_map(|e| {
+                let name = e.file_name();
+                let generation = name.to_str()?.strip_prefix("journal.")?;
+                generation.parse::<u64>().ok()
+            })
+            .filter(|generation| *generation >= first)
+            .collect::<Vec<u64>>();
+
+        generations.sort_unstable();
+
+        Ok(generations)
+    }
+
+    /// Reads every intact record of a generation, truncating the file after the last complete
+    /// frame. A file whose header was torn is emptied, so reopening it writes the header again,
+    /// and one with a damaged header is moved aside whole.
+    pub fn replay(generation: u64) -> Result<Replay, std::io::Error> {
+        let path = journal_file(generation)?;
+        let bytes = std::fs::read(&path)?;
+
+        if bytes.len() >= HEADER_SIZE && payload_of(&bytes).is_err() {
+            std::fs::rename(&path, damaged_journal_file(generation)?)?;
+
+            return Ok(Replay {
+                records: Vec::new(),
+                is_damaged: true,
+            });
+        }
+
+        let (records, end, skipped) = match bytes.get(HEADER_SIZE..) {
+            Some(frames) => {
+                let (records, length, skipped) = Self::decode_frames(frames);
+                (records, HEADER_SIZE + length, skipped)
+            }
+            None => (Vec::new(), 0, 0),
+        };
+
+        let is_damaged = skipped > 0 || (end > 0 && end < bytes.len());
+        if is_damaged {
+            std::fs::copy(&path, damaged_journal_file(generation)?)?;
+        }
+
+        if end < bytes.len() {
+            let file = OpenOptions::new().write(true).open(path)?;
+            file.set_len(end as u64)?;
+            file.sync_all()?;
+        }
+
+        Ok(Replay {
+            records,
+            is_damaged,
+        })
+    }
+
+    pub fn remove_before(generation: u64) -> Result<(), std::io::Error> {
+        Self::generations_from(0)?
+            .into_iter()
+            .filter(|g| *g < generation)
+            .try_for_each(|g| std::fs::remove_file(journal_file(g)?))
+    }
+
+    fn encode_frame(record: &JournalRecord) -> Result<Vec<u8>, std::io::Error> {
+        let payload = postcard::to_stdvec(record)
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
+        let length = u32::try_from(payload.len())
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
+
+        let mut frame = Vec::with_capacity(RECORD_HEADER_SIZE + payload.len());
+        frame.extend(length.to_le_bytes());
+        frame.extend(crc32fast::hash(&payload).to_le_bytes());
+        frame.extend(payload);
+
+        Ok(frame)
+    }
+
+    /// Decodes frames up to the first incomplete one. Returns the records, the length of the
+    /// complete frames and how many of them were damaged and skipped.
+    fn decode_frames(bytes: &[u8]) -> (Vec<JournalRecord>, usize, usize) {
+        let mut records = Vec::new();
+        let mut offset = 0;
+        let mut skipped = 0;
+        while let Some((record, length)) = Self::decode_frame(&bytes[offset..]) {
+            match record {
+                Some(record) => records.push(record),
+                None => skipped += 1,
+            }
+            offset += length;
+        }
+
+        (records, offset, skipped)
+    }
+
+    /// `None` when the frame is cut off, a record of `None` when it is complete but its CRC or
+    /// contents do not match.
+    fn decode_frame(bytes: &[u8]) -> Option<(Option<JournalRecord>, usize)> {
+        let length = u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?) as usize;
+        let crc = u32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?);
+        let payload = bytes.get(RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + length)?;
+
+        let record = Some(payload)
+            .filter(|payload| crc32fast::hash(payload) == crc)
+            .and_then(|payload| postcard::from_bytes(payload).ok());
+
+        Some((record, RECORD_HEADER_SIZE + length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(path: &str, pinned: bool) -> Vec<u8> {
+        let record = JournalRecord::Pin(PathBuf::from(path), SystemTime::UNIX_EPOCH, pinned);
+
+        Journal::encode_frame(&record).unwrap()
+    }
+
+    fn paths(records: &[JournalRecord]) -> Vec<&str> {
+        records
+            .iter()
+            .map(|record| match record {
+                JournalRecord::Pin(path, _, _) => path.to_str().unwrap(),
+                _ => panic!("only pins were written"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decode_frame_reads_what_append_writes() {
+        let bytes = frame("a.ck3", true);
+
+        let Some((Some(JournalRecord::Pin(path, time, true)), length)) =
+            Journal::decode_frame(&bytes)
+        else {
+            panic!("the frame was not read back");
+        };
+
+        assert_eq!(path, PathBuf::from("a.ck3"));
+        assert_eq!(time, SystemTime::UNIX_EPOCH);
+        assert_eq!(length, bytes.len());
+    }
+
+    #[test]
+    fn decode_frame_rejects_a_bad_crc() {
+        let mut bytes = frame("a.ck3", true);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        let Some((record, length)) = Journal::decode_frame(&bytes) else {
+            panic!("a complete frame was taken as cut off");
+        };
+
+        assert!(record.is_none());
+        assert_eq!(length, bytes.len());
+    }
+
+    #[test]
+    fn decode_frame_stops_at_a_cut_off_frame() {
+        let bytes = frame("a.ck3", true);
+
+        assert!(Journal::decode_frame(&bytes[..bytes.len() - 1]).is_none());
+        assert!(Journal::decode_frame(&bytes[..RECORD_HEADER_SIZE - 1]).is_none());
+        assert!(Journal::decode_frame(&[]).is_none());
+    }
+
+    #[test]
+    fn decode_frames_ends_before_a_torn_tail() {
+        let mut bytes = [frame("a.ck3", true), frame("b.ck3", false)].concat();
+        let complete = bytes.len();
+        bytes.extend(&frame("c.ck3", true)[..5]);
+
+        let (records, length, skipped) = Journal::decode_frames(&bytes);
+
+        assert_eq!(paths(&records), ["a.ck3", "b.ck3"]);
+        assert_eq!(length, complete);
+        assert_eq!(skipped, 0);
+    }
+
+    #[test]
+    fn decode_frames_skips_a_damaged_frame() {
+        let mut damaged = frame("b.ck3", true);
+        damaged[RECORD_HEADER_SIZE] ^= 0xff;
+        let bytes = [frame("a.ck3", true), damaged, frame("c.ck3", false)].concat();
+
+        let (records, length, skipped) = Journal::decode_frames(&bytes);
+
+        assert_eq!(paths(&records), ["a.ck3", "c.ck3"]);
+        assert_eq!(length, bytes.len());
+        assert_eq!(skipped, 1);
+    }
+}
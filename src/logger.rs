@@ -0,0 +1,161 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::path::{log_directory, log_file};
+
+/// The current log file is rotated once it grows past this size.
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+/// Rotated log files kept next to the current one, numbered from the newest.
+const MAX_ROTATED_LOGS: usize = 3;
+
+static LOGGER: OnceLock<FileLogger> = OnceLock::new();
+
+struct FileLogger {
+    path: PathBuf,
+    file: Mutex<(File, u64)>,
+}
+
+impl FileLogger {
+    fn open(path: PathBuf) -> Result<Self, std::io::Error> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            file: Mutex::new((file, size)),
+        })
+    }
+
+    /// Shifts every rotated file one number up, dropping the oldest, and starts an empty log.
+    fn rotate(&self, file: &mut (File, u64)) -> Result<(), std::io::Error> {
+        for number in (1..MAX_ROTATED_LOGS).rev() {
+            let from = rotated_path(&self.path, number);
+            if from.exists() {
+                std::fs::rename(from, rotated_path(&self.path, number + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+
+        *file = (File::create(&self.path)?, 0);
+
+        Ok(())
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        // Messages spanning several lines are kept on one, so every line of the file is an entry.
+        let message = record.args().to_string().replace('\n', " ");
+        let line = format!(
+            "{} {:<5} {}: {message}\n",
+            Local::now().format("%Y-%m-%d %T%.3f"),
+            record.level(),
+            record.target(),
+        );
+
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+
+        if file.1 + line.len() as u64 > MAX_LOG_SIZE && self.rotate(&mut file).is_err() {
+            return;
+        }
+
+        if file.0.write_all(line.as_bytes()).is_ok() {
+            file.1 += line.len() as u64;
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.0.flush();
+        }
+    }
+}
+
+pub struct LogEntry {
+    time: String,
+    level: Level,
+    message: String,
+}
+
+impl LogEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(4, ' ');
+        let date = parts.next()?;
+        let time = parts.next()?;
+        let level = Level::from_str(parts.next()?).ok()?;
+        let (_target, message) = parts.next()?.split_once(": ")?;
+
+        Some(Self {
+            time: format!("{date} {time}"),
+            level,
+            message: message.to_owned(),
+        })
+    }
+
+    pub fn time(&self) -> &str {
+        &self.time
+    }
+
+    pub const fn level(&self) -> Level {
+        self.level
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Installs the file logger. Everything from debug level up is written.
+pub fn init() -> Result<(), std::io::Error> {
+    std::fs::create_dir_all(log_directory()?)?;
+
+    let logger = FileLogger::open(log_file()?)?;
+    let logger = LOGGER.get_or_init(|| logger);
+
+    log::set_logger(logger).map_err(|_| std::io::Error::from(std::io::ErrorKind::AlreadyExists))?;
+    log::set_max_level(LevelFilter::Debug);
+
+    Ok(())
+}
+
+/// Size of the current log file, which changes whenever something is logged or the log rotates.
+pub fn log_size() -> Result<u64, std::io::Error> {
+    Ok(std::fs::metadata(log_file()?)?.len())
+}
+
+/// Entries of the current log file at `filter` or more severe, oldest first.
+pub fn read_log(filter: LevelFilter) -> Result<Vec<LogEntry>, std::io::Error> {
+    let text = std::fs::read_to_string(log_file()?)?;
+
+    let entries = text
+        .lines()
+        .filter_map(LogEntry::parse)
+        .filter(|entry| entry.level <= filter)
+        .collect();
+
+    Ok(entries)
+}
+
+fn rotated_path(path: &Path, number: usize) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(format!(".{number}"));
+
+    path.with_file_name(file_name)
+}
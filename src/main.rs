@@ -1,20 +1,42 @@
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::OnceLock;
 
+use log::{Level, log};
+
 use crate::save_file_watcher::{SaveFileUpdate, SaveFileWatcher};
+use crate::ui::notification::{NotificationLevel, push};
 
+mod annotation;
+mod background_task;
+mod checkpoint;
+mod chunk;
+mod codec;
 mod context;
 mod file_op;
+mod gamestate_browser;
+mod journal;
+mod logger;
+mod metadata_backfill;
+mod migration;
+mod object_hash;
+mod object_store;
 mod path;
+mod retention;
 mod save_file;
 mod save_file_event_handler;
 mod save_file_watcher;
+mod save_format;
 mod save_storage;
 mod save_version;
+mod schema;
 mod state;
 mod storage;
+mod storage_recovery;
 mod time_budget;
 mod ui;
+mod verification;
+mod version_entry;
+mod version_store;
 mod watcher_error;
 
 static mut CHANNEL: OnceLock<(Sender<SaveFileUpdate>, Receiver<SaveFileUpdate>)> = OnceLock::new();
@@ -22,6 +44,16 @@ static mut CHANNEL: OnceLock<(Sender<SaveFileUpdate>, Receiver<SaveFileUpdate>)>
 static WATCHER: OnceLock<SaveFileWatcher> = OnceLock::new();
 
 fn main() {
+    // Before the first run after an upgrade the data directory may still be the legacy file, which
+    // is moved aside so that the migration is logged as well.
+    if let Err(e) = migration::move_legacy_save_data().and_then(|()| logger::init()) {
+        push(
+            NotificationLevel::Warning,
+            format!("Log file could not be opened: {e}"),
+        );
+    }
+    log!(Level::Info, "Started {}", env!("CARGO_PKG_VERSION"));
+
     let watcher = SaveFileWatcher::new().expect("Save file watcher initialization failed");
     let _ = WATCHER.set(watcher);
 
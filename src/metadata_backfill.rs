@@ -0,0 +1,25 @@
+use crate::background_task::BackgroundTask;
+use crate::save_format::SaveMetadata;
+use crate::version_entry::{VersionEntry, VersionKey};
+use crate::version_store::VersionStore;
+
+pub type MetadataBackfill = BackgroundTask<Vec<(VersionKey, SaveMetadata)>>;
+
+/// Reads the metadata of versions captured before it was recorded, on a separate thread. A
+/// version that is not a readable save gets empty metadata, so it is not read again on every
+/// start. One whose data cannot be read is left out and tried again next time.
+pub fn backfill_metadata(versions: Vec<(VersionKey, VersionEntry)>) -> MetadataBackfill {
+    BackgroundTask::start(move || {
+        let version_store = VersionStore::open()?;
+
+        let described = versions
+            .into_iter()
+            .filter_map(|(key, entry)| {
+                let data = version_store.read(&entry).ok()?;
+                Some((key, SaveMetadata::parse(&data).unwrap_or_default()))
+            })
+            .collect();
+
+        Ok(described)
+    })
+}
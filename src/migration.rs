@@ -0,0 +1,306 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::annotation::Annotation;
+use crate::checkpoint::Checkpoint;
+use crate::file_op::write_atomically;
+use crate::journal::Journal;
+use crate::path::{backup_index_file, index_file, journal_file, legacy_save_data, save_data};
+use crate::schema::{FORMAT_VERSION, read_header, with_header};
+use crate::storage::InnerType;
+use crate::version_entry::VersionKey;
+use crate::version_store::VersionStore;
+
+pub type LegacyType = HashMap<PathBuf, HashMap<SystemTime, Vec<u8>>>;
+
+/// Checkpoint layout of format versions 1 and 2.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CheckpointV2 {
+    journal_generation: u64,
+    index: InnerType,
+}
+
+/// Checkpoint layout of format versions 3 and 4.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CheckpointV4 {
+    journal_generation: u64,
+    index: InnerType,
+    quarantine: InnerType,
+}
+
+/// Checkpoint layout of format version 5.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CheckpointV5 {
+    journal_generation: u64,
+    index: InnerType,
+    quarantine: InnerType,
+    pinned: HashSet<VersionKey>,
+}
+
+/// Checkpoint layout of format version 6.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CheckpointV6 {
+    journal_generation: u64,
+    index: InnerType,
+    quarantine: InnerType,
+    pinned: HashSet<VersionKey>,
+    annotations: HashMap<VersionKey, Annotation>,
+}
+
+/// Checkpoint layout of format version 7.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CheckpointV7 {
+    journal_generation: u64,
+    index: InnerType,
+    quarantine: InnerType,
+    pinned: HashSet<VersionKey>,
+    annotations: HashMap<VersionKey, Annotation>,
+    undo_points: HashMap<PathBuf, SystemTime>,
+}
+
+type Migration = fn() -> Result<(), std::io::Error>;
+
+/// Migration at index `n` upgrades a database in format version `n` to `n + 1`.
+const MIGRATIONS: [Migration; FORMAT_VERSION as usize] = [
+    split_legacy_save_data,
+    add_headers,
+    add_quarantine,
+    add_removals,
+    add_pins,
+    add_annotations,
+    add_undo_points,
+    add_metadata,
+];
+
+/// Brings the data directory up to `FORMAT_VERSION`. Runs before anything else reads it.
+pub fn migrate() -> Result<(), std::io::Error> {
+    let version = detect_version()?;
+    if version > FORMAT_VERSION {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Save data format {version} is newer than the supported {FORMAT_VERSION}"),
+        ));
+    }
+
+    MIGRATIONS[usize::from(version)..]
+        .iter()
+        .try_for_each(|migration| migration())
+}
+
+/// - 0: every version in a single headerless postcard blob at the data directory path.
+/// - 1: content-addressed objects with a headerless checkpoint and journals.
+/// - 2: as 1, with checkpoint and journal files starting with a header.
+/// - 3: checkpoints also hold quarantined versions.
+/// - 4: journals may also record removed versions.
+/// - 5: checkpoints also hold pinned versions.
+/// - 6: checkpoints also hold version labels and notes.
+/// - 7: checkpoints also hold the version each file can be put back to after a restore.
+/// - 8: checkpoints also hold the metadata read from each version when it was captured.
+fn detect_version() -> Result<u16, std::io::Error> {
+    if save_data()?.is_file() {
+        return Ok(0);
+    }
+
+    for path in data_files()? {
+        if let Ok(bytes) = std::fs::read(path) {
+            return Ok(read_header(&bytes).map_or(1, |(version, _)| version));
+        }
+    }
+
+    // The legacy blob was moved aside, but importing it did not complete.
+    if legacy_save_data()?.exists() {
+        return Ok(0);
+    }
+
+    Ok(FORMAT_VERSION)
+}
+
+pub fn read_legacy_save_data() -> Result<LegacyType, std::io::Error> {
+    let bytes = std::fs::read(legacy_save_data()?)?;
+
+    decode(&bytes)
+}
+
+/// Makes room for the data directory when its path still holds the legacy blob. Also done before
+/// the migrations run, so the log can be written there from the start.
+pub fn move_legacy_save_data() -> Result<(), std::io::Error> {
+    let saves_path = save_data()?;
+    if saves_path.is_file() {
+        std::fs::rename(saves_path, legacy_save_data()?)?;
+    }
+
+    Ok(())
+}
+
+/// Checkpoints followed by every journal file, whether or not they exist.
+fn data_files() -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut paths = vec![index_file()?, backup_index_file()?];
+    for generation in Journal::generations_from(0)? {
+        paths.push(journal_file(generation)?);
+    }
+
+    Ok(paths)
+}
+
+/// The blob is kept aside so it can be imported again should the index ever be lost. One that
+/// cannot be decoded is left without an index, which `Storage` reports.
+fn split_legacy_save_data() -> Result<(), std::io::Error> {
+    move_legacy_save_data()?;
+
+    let Ok(legacy_save_data) = read_legacy_save_data() else {
+        return Ok(());
+    };
+
+    let version_store = VersionStore::open()?;
+
+    let mut index = InnerType::new();
+    for (path, versions) in legacy_save_data {
+        let entries = index.entry(path).or_default();
+        for (time, data) in versions {
+            entries.insert(time, version_store.insert(&data)?);
+        }
+    }
+
+    let checkpoint = CheckpointV2 {
+        journal_generation: 0,
+        index,
+    };
+
+    write_atomically(&index_file()?, &encode(&checkpoint)?)
+}
+
+fn add_headers() -> Result<(), std::io::Error> {
+    for path in data_files()? {
+        let Ok(bytes) = std::fs::read(&path) else {
+            continue;
+        };
+
+        if read_header(&bytes).is_none() {
+            write_atomically(&path, &with_header(2, &bytes))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn add_quarantine() -> Result<(), std::io::Error> {
+    upgrade_data_files(2, |payload| {
+        let previous = decode::<CheckpointV2>(payload)?;
+
+        encode(&CheckpointV4 {
+            journal_generation: previous.journal_generation,
+            index: previous.index,
+            quarantine: InnerType::new(),
+        })
+    })
+}
+
+/// Nothing written so far changes, the header only keeps older builds from reading journals with
+/// removals in them.
+fn add_removals() -> Result<(), std::io::Error> {
+    upgrade_data_files(3, |payload| Ok(payload.to_vec()))
+}
+
+fn add_pins() -> Result<(), std::io::Error> {
+    upgrade_data_files(4, |payload| {
+        let previous = decode::<CheckpointV4>(payload)?;
+
+        encode(&CheckpointV5 {
+            journal_generation: previous.journal_generation,
+            index: previous.index,
+            quarantine: previous.quarantine,
+            pinned: HashSet::new(),
+        })
+    })
+}
+
+fn add_annotations() -> Result<(), std::io::Error> {
+    upgrade_data_files(5, |payload| {
+        let previous = decode::<CheckpointV5>(payload)?;
+
+        encode(&CheckpointV6 {
+            journal_generation: previous.journal_generation,
+            index: previous.index,
+            quarantine: previous.quarantine,
+            pinned: previous.pinned,
+            annotations: HashMap::new(),
+        })
+    })
+}
+
+fn add_undo_points() -> Result<(), std::io::Error> {
+    upgrade_data_files(6, |payload| {
+        let previous = decode::<CheckpointV6>(payload)?;
+
+        encode(&CheckpointV7 {
+            journal_generation: previous.journal_generation,
+            index: previous.index,
+            quarantine: previous.quarantine,
+            pinned: previous.pinned,
+            annotations: previous.annotations,
+            undo_points: HashMap::new(),
+        })
+    })
+}
+
+/// Versions captured before are read again by `MetadataBackfill` once the history is open.
+fn add_metadata() -> Result<(), std::io::Error> {
+    upgrade_data_files(7, |payload| {
+        let previous = decode::<CheckpointV7>(payload)?;
+
+        encode(&Checkpoint {
+            journal_generation: previous.journal_generation,
+            index: previous.index,
+            quarantine: previous.quarantine,
+            pinned: previous.pinned,
+            annotations: previous.annotations,
+            undo_points: previous.undo_points,
+            metadata: HashMap::new(),
+        })
+    })
+}
+
+/// Rewrites the files in format version `from` with the header of the next one. Journal records
+/// are kept as they are, since new kinds of records are only ever added. Checkpoints go through
+/// `upgrade_checkpoint`, and the ones that cannot be decoded are left for the recovery on startup
+/// to deal with.
+fn upgrade_data_files(
+    from: u16,
+    upgrade_checkpoint: fn(&[u8]) -> Result<Vec<u8>, std::io::Error>,
+) -> Result<(), std::io::Error> {
+    let checkpoint_paths = [index_file()?, backup_index_file()?];
+
+    for path in data_files()? {
+        let Ok(bytes) = std::fs::read(&path) else {
+            continue;
+        };
+        let Some((version, payload)) = read_header(&bytes) else {
+            continue;
+        };
+        if version != from {
+            continue;
+        }
+
+        let payload = if checkpoint_paths.contains(&path) {
+            let Ok(payload) = upgrade_checkpoint(payload) else {
+                continue;
+            };
+            payload
+        } else {
+            payload.to_vec()
+        };
+
+        write_atomically(&path, &with_header(from + 1, &payload))?;
+    }
+
+    Ok(())
+}
+
+fn decode<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, std::io::Error> {
+    postcard::from_bytes(bytes).map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))
+}
+
+fn encode<T: serde::Serialize>(value: &T) -> Result<Vec<u8>, std::io::Error> {
+    postcard::to_stdvec(value).map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))
+}
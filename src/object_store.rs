@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+
+use crate::codec::Codec;
+use crate::file_op::write_atomically;
+use crate::object_hash::ObjectHash;
+
+/// Objects are addressed by the hash of their original bytes. The codec an object was written
+/// with is recorded in its file extension, so objects written with any codec stay readable.
+pub struct ObjectStore {
+    root: PathBuf,
+    codec: Codec,
+}
+
+impl ObjectStore {
+    pub fn open(root: PathBuf, codec: Codec) -> Result<Self, std::io::Error> {
+        std::fs::create_dir_all(&root)?;
+
+        Ok(Self { root, codec })
+    }
+
+    pub fn insert(&self, data: &[u8]) -> Result<ObjectHash, std::io::Error> {
+        let hash = ObjectHash::of(data);
+        self.write(&hash, data)?;
+
+        Ok(hash)
+    }
+
+    pub fn write(&self, hash: &ObjectHash, data: &[u8]) -> Result<(), std::io::Error> {
+        if self.contains(hash) {
+            return Ok(());
+        }
+
+        let path = self.path_of(hash, self.codec);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        write_atomically(&path, &self.codec.encode(data)?)
+    }
+
+    pub fn contains(&self, hash: &ObjectHash) -> bool {
+        Codec::ALL
+            .into_iter()
+            .any(|codec| self.path_of(hash, codec).exists())
+    }
+
+    pub fn read(&self, hash: &ObjectHash) -> Result<Vec<u8>, std::io::Error> {
+        for codec in Codec::ALL {
+            match std::fs::read(self.path_of(hash, codec)) {
+                Ok(data) => return codec.decode(data),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+    }
+
+    /// Size of the object as stored on disk, after encoding.
+    pub fn size_of(&self, hash: &ObjectHash) -> Result<u64, std::io::Error> {
+        for codec in Codec::ALL {
+            match std::fs::metadata(self.path_of(hash, codec)) {
+                Ok(metadata) => return Ok(metadata.len()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+    }
+
+    /// Removing an object that does not exist is not an error.
+    pub fn remove(&self, hash: &ObjectHash) -> Result<(), std::io::Error> {
+        for codec in Codec::ALL {
+            match std::fs::remove_file(self.path_of(hash, codec)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn path_of(&self, hash: &ObjectHash, codec: Codec) -> PathBuf {
+        let name = hash.to_string();
+        let (prefix, rest) = name.split_at(2);
+        let path = self.root.join(prefix).join(rest);
+
+        match codec.extension() {
+            Some(extension) => path.with_extension(extension),
+            None => path,
+        }
+    }
+}
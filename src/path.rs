@@ -22,3 +22,65 @@ pub fn save_data() -> Result<PathBuf, std::io::Error> {
 
     Ok(path)
 }
+
+pub fn index_file() -> Result<PathBuf, std::io::Error> {
+    Ok(save_data()?.join("index"))
+}
+
+pub fn backup_index_file() -> Result<PathBuf, std::io::Error> {
+    Ok(save_data()?.join("index.bak"))
+}
+
+pub fn damaged_index_file() -> Result<PathBuf, std::io::Error> {
+    Ok(save_data()?.join("index.damaged"))
+}
+
+pub fn journal_file(generation: u64) -> Result<PathBuf, std::io::Error> {
+    Ok(save_data()?.join(format!("journal.{generation}")))
+}
+
+/// A journal that could not be fully replayed, as it was before it was repaired.
+pub fn damaged_journal_file(generation: u64) -> Result<PathBuf, std::io::Error> {
+    Ok(save_data()?.join(format!("journal.{generation}.damaged")))
+}
+
+pub fn objects_directory() -> Result<PathBuf, std::io::Error> {
+    Ok(save_data()?.join("objects"))
+}
+
+pub fn manifests_directory() -> Result<PathBuf, std::io::Error> {
+    Ok(save_data()?.join("manifests"))
+}
+
+pub fn log_directory() -> Result<PathBuf, std::io::Error> {
+    Ok(save_data()?.join("logs"))
+}
+
+pub fn log_file() -> Result<PathBuf, std::io::Error> {
+    Ok(log_directory()?.join("ck3-savescummer.log"))
+}
+
+/// Supplied by the user to read binary saves, see `TokenTable`.
+pub fn token_file() -> Result<PathBuf, std::io::Error> {
+    Ok(save_data()?.join("tokens.txt"))
+}
+
+/// Written by the user to have old versions pruned, see `RetentionPolicy`.
+pub fn retention_file() -> Result<PathBuf, std::io::Error> {
+    Ok(save_data()?.join("retention.txt"))
+}
+
+pub fn legacy_save_data() -> Result<PathBuf, std::io::Error> {
+    Ok(save_data()?.with_extension("legacy"))
+}
+
+/// Next to the save games directory, so exported versions are easy to find but are not taken for
+/// new save files.
+pub fn export_directory() -> Result<PathBuf, std::io::Error> {
+    let save_directory_path = save_directory()?;
+    let parent = save_directory_path
+        .parent()
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?;
+
+    Ok(parent.join("save exports"))
+}
@@ -0,0 +1,279 @@
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+use crate::path::retention_file;
+
+const MINUTE: Duration = Duration::from_secs(60);
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Keeps the versions captured within `span`, thinned out to the newest one per `interval`. A
+/// zero interval keeps all of them.
+pub struct RetentionBucket {
+    pub span: Duration,
+    pub interval: Duration,
+}
+
+impl RetentionBucket {
+    fn slot_of(&self, time: SystemTime) -> u128 {
+        let since_epoch = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+
+        since_epoch.as_nanos() / self.interval.as_nanos().max(1)
+    }
+}
+
+/// A version is kept while any of the rules keeps it, and every version is kept when there are
+/// no rules, which is the default. The disk budget is applied to whatever the rules keep, removing
+/// the oldest versions first.
+#[derive(Default)]
+pub struct RetentionPolicy {
+    pub keep_last: Option<usize>,
+    pub buckets: Vec<RetentionBucket>,
+    pub disk_budget: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Reads the rules from one line each, `#` starting a comment:
+    ///
+    /// ```text
+    /// keep_last 10
+    /// # span and interval, everything from the last hour and one per hour for a day
+    /// bucket 1h 0
+    /// bucket 1d 1h
+    /// disk_budget 5G
+    /// ```
+    ///
+    /// Durations take an `s`, `m`, `h` or `d` suffix, sizes a `K`, `M` or `G` one.
+    pub fn parse(text: &str) -> Result<Self, std::io::Error> {
+        let mut policy = Self::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let malformed = || {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Malformed retention rule on line {}", number + 1),
+                )
+            };
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+            match words[..] {
+                ["keep_last", count] => {
+                    policy.keep_last = Some(count.parse().map_err(|_| malformed())?);
+                }
+                ["bucket", span, interval] => policy.buckets.push(RetentionBucket {
+                    span: parse_duration(span).ok_or_else(malformed)?,
+                    interval: parse_duration(interval).ok_or_else(malformed)?,
+                }),
+                ["disk_budget", size] => {
+                    policy.disk_budget = Some(parse_size(size).ok_or_else(malformed)?);
+                }
+                _ => return Err(malformed()),
+            }
+        }
+
+        Ok(policy)
+    }
+
+    /// The rules in the data directory, or none at all when there is no such file.
+    pub fn read() -> Result<Self, std::io::Error> {
+        match std::fs::read_to_string(retention_file()?) {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Capture times of the versions of a file that no rule keeps. The newest version is always
+    /// kept, so a file never loses its whole history.
+    pub fn expired(
+        &self,
+        times: impl Iterator<Item = SystemTime>,
+        now: SystemTime,
+    ) -> Vec<SystemTime> {
+        if self.keep_last.is_none() && self.buckets.is_empty() {
+            return Vec::new();
+        }
+
+        let mut times = times.collect::<Vec<SystemTime>>();
+        times.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut kept = times
+            .iter()
+            .take(self.keep_last.unwrap_or(0).max(1))
+            .copied()
+            .collect::<HashSet<SystemTime>>();
+
+        for bucket in &self.buckets {
+            let mut slots = HashSet::new();
+            for time in &times {
+                if now.duration_since(*time).unwrap_or_default() > bucket.span {
+                    break;
+                }
+
+                if slots.insert(bucket.slot_of(*time)) {
+                    kept.insert(*time);
+                }
+            }
+        }
+
+        times.retain(|time| !kept.contains(time));
+
+        times
+    }
+}
+
+fn parse_duration(text: &str) -> Option<Duration> {
+    if text == "0" {
+        return Some(Duration::ZERO);
+    }
+
+    let unit = match text.chars().last()? {
+        's' => Duration::from_secs(1),
+        'm' => MINUTE,
+        'h' => HOUR,
+        'd' => DAY,
+        _ => return None,
+    };
+
+    unit.checked_mul(text[..text.len() - 1].parse().ok()?)
+}
+
+fn parse_size(text: &str) -> Option<u64> {
+    let (digits, unit) = match text.chars().last()? {
+        'K' => (&text[..text.len() - 1], 1 << 10),
+        'M' => (&text[..text.len() - 1], 1 << 20),
+        'G' => (&text[..text.len() - 1], 1 << 30),
+        _ => (text, 1),
+    };
+
+    digits.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times(now: SystemTime, ages: &[Duration]) -> Vec<SystemTime> {
+        ages.iter().map(|age| now - *age).collect()
+    }
+
+    fn ages(now: SystemTime, times: Vec<SystemTime>) -> Vec<Duration> {
+        let mut ages = times
+            .into_iter()
+            .map(|time| now.duration_since(time).unwrap())
+            .collect::<Vec<Duration>>();
+        ages.sort_unstable();
+
+        ages
+    }
+
+    #[test]
+    fn no_rules_keep_everything() {
+        let now = SystemTime::now();
+        let times = times(now, &[MINUTE, 40 * DAY, 400 * DAY]);
+
+        assert!(
+            RetentionPolicy::default()
+                .expired(times.into_iter(), now)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn keep_last_keeps_the_newest() {
+        let now = SystemTime::now();
+        let policy = RetentionPolicy {
+            keep_last: Some(2),
+            ..RetentionPolicy::default()
+        };
+        let times = times(now, &[3 * DAY, MINUTE, 2 * DAY, HOUR]);
+
+        let expired = policy.expired(times.into_iter(), now);
+
+        assert_eq!(ages(now, expired), [2 * DAY, 3 * DAY]);
+    }
+
+    #[test]
+    fn bucket_keeps_one_per_interval_within_its_span() {
+        let now = SystemTime::UNIX_EPOCH + 1000 * DAY;
+        let policy = RetentionPolicy {
+            keep_last: Some(1),
+            buckets: vec![RetentionBucket {
+                span: 3 * DAY,
+                interval: DAY,
+            }],
+            disk_budget: None,
+        };
+        let times = times(now, &[HOUR, 2 * HOUR, DAY + HOUR, DAY + 2 * HOUR, 5 * DAY]);
+
+        let expired = policy.expired(times.into_iter(), now);
+
+        assert_eq!(ages(now, expired), [2 * HOUR, DAY + 2 * HOUR, 5 * DAY]);
+    }
+
+    #[test]
+    fn zero_interval_keeps_everything_within_the_span() {
+        let now = SystemTime::now();
+        let policy = RetentionPolicy {
+            keep_last: None,
+            buckets: vec![RetentionBucket {
+                span: HOUR,
+                interval: Duration::ZERO,
+            }],
+            disk_budget: None,
+        };
+        let times = times(now, &[MINUTE, 2 * MINUTE, 3 * MINUTE, 2 * HOUR]);
+
+        let expired = policy.expired(times.into_iter(), now);
+
+        assert_eq!(ages(now, expired), [2 * HOUR]);
+    }
+
+    #[test]
+    fn newest_is_always_kept() {
+        let now = SystemTime::now();
+        let policy = RetentionPolicy {
+            keep_last: Some(0),
+            buckets: vec![RetentionBucket {
+                span: HOUR,
+                interval: HOUR,
+            }],
+            disk_budget: None,
+        };
+        let times = times(now, &[40 * DAY, 50 * DAY]);
+
+        let expired = policy.expired(times.into_iter(), now);
+
+        assert_eq!(ages(now, expired), [50 * DAY]);
+    }
+
+    #[test]
+    fn parse_reads_every_rule() {
+        let text = "# prune\nkeep_last 10\nbucket 1h 0\nbucket 30d 1d # daily\n\ndisk_budget 5G\n";
+
+        let policy = RetentionPolicy::parse(text).unwrap();
+
+        assert_eq!(policy.keep_last, Some(10));
+        assert_eq!(policy.buckets.len(), 2);
+        assert_eq!(policy.buckets[0].span, HOUR);
+        assert_eq!(policy.buckets[0].interval, Duration::ZERO);
+        assert_eq!(policy.buckets[1].span, 30 * DAY);
+        assert_eq!(policy.buckets[1].interval, DAY);
+        assert_eq!(policy.disk_budget, Some(5 << 30));
+    }
+
+    #[test]
+    fn parse_names_the_malformed_line() {
+        let Err(error) = RetentionPolicy::parse("keep_last 10\nbucket 1w 1d\n") else {
+            panic!("an unknown unit was accepted");
+        };
+
+        assert!(error.to_string().contains("line 2"));
+    }
+}
@@ -21,13 +21,12 @@ impl SaveFile {
 
 impl PartialOrd for SaveFile {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.1.cmp(&other.1))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for SaveFile {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other)
-            .expect("Under no circumstances SaveFile::cmp should throw")
+        self.1.cmp(&other.1)
     }
 }
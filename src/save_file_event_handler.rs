@@ -1,3 +1,4 @@
+use std::ptr::addr_of;
 use std::sync::mpsc;
 use std::sync::mpsc::Sender;
 
@@ -6,8 +7,9 @@ use notify::event::{CreateKind, ModifyKind};
 use notify::EventKind;
 
 use crate::CHANNEL;
-use crate::file_op::gather_file_data;
+use crate::file_op::{gather_file_data, is_temporary};
 use crate::save_file_watcher::SaveFileUpdate;
+use crate::ui::notification::{NotificationLevel, push};
 
 pub struct SaveFileEventListener {
     sender: &'static Sender<SaveFileUpdate>,
@@ -16,7 +18,7 @@ pub struct SaveFileEventListener {
 impl SaveFileEventListener {
     pub fn new() -> Self {
         Self {
-            sender: &unsafe { CHANNEL.get_or_init(mpsc::channel) }.0,
+            sender: &unsafe { &*addr_of!(CHANNEL) }.get_or_init(mpsc::channel).0,
         }
     }
 }
@@ -25,6 +27,7 @@ impl notify::EventHandler for SaveFileEventListener {
     fn handle_event(&mut self, event: notify::Result<notify::Event>) {
         let Ok(event) = event else {
             log!(Level::Error, "Event receive failed: {event:?}");
+            push(NotificationLevel::Error, "Watching save files failed");
             return;
         };
 
@@ -38,9 +41,16 @@ impl notify::EventHandler for SaveFileEventListener {
         event
             .paths
             .iter()
-            .flat_map(gather_file_data)
-            .for_each(|event| {
-                let _ = self.sender.send(event);
+            .filter(|path| !is_temporary(path))
+            .for_each(|path| match gather_file_data(path) {
+                Ok(update) => {
+                    log!(Level::Debug, "Read {} for a new version", path.display());
+                    let _ = self.sender.send(update);
+                }
+                Err(e) => push(
+                    NotificationLevel::Warning,
+                    format!("{} could not be read: {e}", path.display()),
+                ),
             });
     }
 }
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::path::token_file;
+use crate::save_format::invalid_data;
+use crate::save_format::parser::{Parser, Token};
+use crate::save_format::tree::{ScalarData, Span, Tree, TreeBuilder};
+use crate::ui::notification::{NotificationLevel, push};
+
+const EQUALS: u16 = 0x0001;
+const OPEN: u16 = 0x0003;
+const CLOSE: u16 = 0x0004;
+const I32: u16 = 0x000c;
+const F32: u16 = 0x000d;
+const BOOL: u16 = 0x000e;
+const QUOTED: u16 = 0x000f;
+const U32: u16 = 0x0014;
+const UNQUOTED: u16 = 0x0017;
+const F64: u16 = 0x0167;
+const U64: u16 = 0x029c;
+const I64: u16 = 0x0317;
+
+static TOKEN_TABLE: OnceLock<Option<TokenTable>> = OnceLock::new();
+
+/// Names of the field tokens of the binary format, which the game does not ship. The file holds
+/// one token per line as its id, in decimal or `0x` hex, and its name separated by whitespace.
+/// Empty lines and lines starting with `#` are skipped.
+pub struct TokenTable(HashMap<u16, String>);
+
+impl TokenTable {
+    pub fn parse(text: &str) -> Result<Self, std::io::Error> {
+        let mut names = HashMap::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let malformed = || invalid_data(format!("Malformed token on line {}", number + 1));
+            let (id, name) = line.split_once(char::is_whitespace).ok_or_else(malformed)?;
+            let id = match id.strip_prefix("0x") {
+                Some(hex) => u16::from_str_radix(hex, 16),
+                None => id.parse(),
+            }
+            .map_err(|_| malformed())?;
+
+            names.insert(id, name.trim().to_owned());
+        }
+
+        Ok(Self(names))
+    }
+
+    /// The table in the data directory, read once on first use. `None` when there is no table or
+    /// it cannot be read, in which case tokens show their ids.
+    pub fn shared() -> Option<&'static Self> {
+        TOKEN_TABLE.get_or_init(Self::read).as_ref()
+    }
+
+    /// Tells the user how reading the file went, as changes to it only apply after a restart.
+    fn read() -> Option<Self> {
+        let path = token_file().ok()?;
+        let table = match std::fs::read_to_string(&path).and_then(|text| Self::parse(&text)) {
+            Ok(table) => table,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                push(
+                    NotificationLevel::Warning,
+                    format!(
+                        "{} was not read, binary saves show token ids until it is fixed and the program is restarted: {e}",
+                        path.display()
+                    ),
+                );
+                return None;
+            }
+        };
+
+        push(
+            NotificationLevel::Info,
+            format!(
+                "Read {} tokens from {}, changes to it apply after a restart",
+                table.0.len(),
+                path.display()
+            ),
+        );
+
+        Some(table)
+    }
+
+    pub fn name_of(&self, id: u16) -> Option<&str> {
+        self.0.get(&id).map(String::as_str)
+    }
+}
+
+/// Splits the binary format into tokens as it goes. Numbers are decoded, strings point into the
+/// data. Data cut off in the middle of a token ends the stream, also for the parser asking for
+/// more after it ended.
+struct BinaryLexer<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BinaryLexer<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let Some(bytes) = self.data.get(self.position..self.position + N) else {
+            self.position = self.data.len();
+            return None;
+        };
+        self.position += N;
+
+        bytes.try_into().ok()
+    }
+
+    fn take_span(&mut self) -> Option<Span> {
+        let length = usize::from(u16::from_le_bytes(self.take()?));
+        let start = self.position;
+        if start + length > self.data.len() {
+            self.position = self.data.len();
+            return None;
+        }
+        self.position += length;
+
+        Some(Span::new(start, self.position))
+    }
+}
+
+impl Iterator for BinaryLexer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let code = u16::from_le_bytes(self.take()?);
+
+        let scalar = match code {
+            EQUALS => return Some(Token::Equals),
+            OPEN => return Some(Token::Open),
+            CLOSE => return Some(Token::Close),
+            I32 => ScalarData::Integer(i64::from(i32::from_le_bytes(self.take()?))),
+            U32 => ScalarData::Unsigned(u64::from(u32::from_le_bytes(self.take()?))),
+            I64 => ScalarData::Integer(i64::from_le_bytes(self.take()?)),
+            U64 => ScalarData::Unsigned(u64::from_le_bytes(self.take()?)),
+            BOOL => ScalarData::Bool(self.take::<1>()?[0] != 0),
+            // Both kinds of floats are fixed point numbers.
+            F32 => ScalarData::Float(f64::from(i32::from_le_bytes(self.take()?)) / 1000.0),
+            F64 => ScalarData::Float(i64::from_le_bytes(self.take()?) as f64 / 100_000.0),
+            QUOTED => ScalarData::Quoted(self.take_span()?),
+            UNQUOTED => ScalarData::Text(self.take_span()?),
+            id => ScalarData::Token(id),
+        };
+
+        Some(Token::Scalar(scalar))
+    }
+}
+
+/// Parses data in the binary format, naming tokens from `names` when there is a table.
+pub fn parse<'a>(
+    data: &'a [u8],
+    names: Option<&'static TokenTable>,
+) -> Result<Tree<'a>, std::io::Error> {
+    let builder = TreeBuilder::new(data, names)?;
+
+    Ok(Parser::new(BinaryLexer::new(data), builder).parse())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: u16 = 0x2d00;
+    const OTHER_KEY: u16 = 0x2d01;
+
+    fn field(key: u16, code: u16, value: &[u8]) -> Vec<u8> {
+        [
+            &key.to_le_bytes(),
+            &EQUALS.to_le_bytes(),
+            &code.to_le_bytes(),
+            value,
+        ]
+        .concat()
+    }
+
+    fn span(text: &str) -> Vec<u8> {
+        let length = u16::try_from(text.len()).unwrap().to_le_bytes();
+
+        [&length, text.as_bytes()].concat()
+    }
+
+    fn parsed(data: &[u8], names: Option<&'static TokenTable>) -> String {
+        format!("{:?}", parse(data, names).unwrap().root())
+    }
+
+    #[test]
+    fn parse_reads_decimal_and_hex_ids() {
+        let table = TokenTable::parse("# comment\n\n11520 date\n  0x2d01\truler  \n").unwrap();
+
+        assert_eq!(table.name_of(KEY), Some("date"));
+        assert_eq!(table.name_of(OTHER_KEY), Some("ruler"));
+        assert_eq!(table.name_of(0x2d02), None);
+    }
+
+    #[test]
+    fn parse_names_the_malformed_line() {
+        for text in ["1 a\nx b\n", "1 a\n0xzz b\n", "1 a\n70000 b\n", "1 a\n2\n"] {
+            let Err(error) = TokenTable::parse(text) else {
+                panic!("{text:?} was accepted");
+            };
+
+            assert_eq!(error.to_string(), "Malformed token on line 2");
+        }
+    }
+
+    #[test]
+    fn shows_token_ids_without_a_table() {
+        let data = field(KEY, I32, &7_i32.to_le_bytes());
+
+        assert_eq!(parsed(&data, None), "{0x2d00=7}");
+    }
+
+    #[test]
+    fn names_tokens_from_the_table() {
+        let table = Box::leak(Box::new(TokenTable::parse("0x2d00 date").unwrap()));
+        let data = [
+            field(KEY, I32, &7_i32.to_le_bytes()),
+            field(OTHER_KEY, BOOL, &[1]),
+        ]
+        .concat();
+
+        assert_eq!(parsed(&data, Some(table)), "{date=7 0x2d01=yes}");
+    }
+
+    #[test]
+    fn decodes_numbers() {
+        let data = [
+            field(KEY, I32, &(-5_i32).to_le_bytes()),
+            field(KEY, U32, &u32::MAX.to_le_bytes()),
+            field(KEY, I64, &i64::MIN.to_le_bytes()),
+            field(KEY, U64, &u64::MAX.to_le_bytes()),
+            field(KEY, BOOL, &[0]),
+        ]
+        .concat();
+
+        assert_eq!(
+            parsed(&data, None),
+            format!(
+                "{{0x2d00=-5 0x2d00={} 0x2d00={} 0x2d00={} 0x2d00=no}}",
+                u32::MAX,
+                i64::MIN,
+                u64::MAX
+            )
+        );
+    }
+
+    #[test]
+    fn scales_fixed_point_floats() {
+        let data = [
+            field(KEY, F32, &1500_i32.to_le_bytes()),
+            field(KEY, F32, &(-250_i32).to_le_bytes()),
+            field(KEY, F64, &(-250_000_i64).to_le_bytes()),
+            field(KEY, F64, &12_345_i64.to_le_bytes()),
+        ]
+        .concat();
+
+        assert_eq!(
+            parsed(&data, None),
+            "{0x2d00=1.5 0x2d00=-0.25 0x2d00=-2.5 0x2d00=0.12345}"
+        );
+    }
+
+    #[test]
+    fn decodes_strings_as_spans_of_the_data() {
+        let data = [
+            field(KEY, QUOTED, &span("x \\\"y\\\"")),
+            field(KEY, UNQUOTED, &span("abc")),
+            field(KEY, QUOTED, &span("")),
+        ]
+        .concat();
+
+        assert_eq!(parsed(&data, None), "{0x2d00=x \"y\" 0x2d00=abc 0x2d00=}");
+    }
+
+    #[test]
+    fn reads_blocks() {
+        let data = [
+            &KEY.to_le_bytes()[..],
+            &EQUALS.to_le_bytes(),
+            &OPEN.to_le_bytes(),
+            &I32.to_le_bytes(),
+            &1_i32.to_le_bytes(),
+            &I32.to_le_bytes(),
+            &2_i32.to_le_bytes(),
+            &CLOSE.to_le_bytes(),
+        ]
+        .concat();
+
+        assert_eq!(parsed(&data, None), "{0x2d00=[1 2]}");
+    }
+
+    #[test]
+    fn ends_at_truncated_input() {
+        let complete = field(KEY, I32, &1_i32.to_le_bytes());
+        let truncated_number = [&complete[..], &field(KEY, I64, &[0; 4])].concat();
+        let truncated_string = [&complete[..], &field(KEY, QUOTED, &span("abc")[..4])].concat();
+        let truncated_code = [&complete[..], &[0x0c]].concat();
+
+        for data in [truncated_number, truncated_string, truncated_code] {
+            assert_eq!(parsed(&data, None), "{0x2d00=1}");
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::save_format::binary::TokenTable;
+use crate::save_format::reader::SaveReader;
+use crate::save_format::tree::{Entry, Tree};
+
+mod binary;
+mod parser;
+mod reader;
+mod text;
+pub mod tree;
+
+/// `SAV`, two version digits, the kind and a random number as hex, the length of the metadata
+/// section as hex and a line break.
+const HEADER_LENGTH: usize = 24;
+
+const MAGIC: &[u8] = b"SAV";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SaveEncoding {
+    Text,
+    Binary,
+}
+
+/// The first line of every save.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SaveHeader {
+    encoding: SaveEncoding,
+    is_compressed: bool,
+    metadata_length: usize,
+}
+
+impl SaveHeader {
+    pub fn parse(data: &[u8]) -> Result<Self, std::io::Error> {
+        let header = data
+            .get(..HEADER_LENGTH)
+            .filter(|header| header.starts_with(MAGIC))
+            .ok_or_else(|| invalid_data("Not a CK3 save"))?;
+
+        let (encoding, is_compressed) = match parse_hex(&header[5..7])? {
+            0 => (SaveEncoding::Text, false),
+            1 => (SaveEncoding::Binary, false),
+            2 => (SaveEncoding::Text, true),
+            3 => (SaveEncoding::Binary, true),
+            kind => return Err(invalid_data(format!("Unknown save kind {kind}"))),
+        };
+
+        Ok(Self {
+            encoding,
+            is_compressed,
+            metadata_length: parse_hex(&header[15..23])?,
+        })
+    }
+
+    pub const fn encoding(&self) -> SaveEncoding {
+        self.encoding
+    }
+
+    /// Compressed saves keep the metadata uncompressed after the header, followed by a zip
+    /// archive with the gamestate.
+    pub const fn is_compressed(&self) -> bool {
+        self.is_compressed
+    }
+
+    /// The metadata section follows the header. In uncompressed saves the gamestate starts with
+    /// it too.
+    pub fn metadata<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        let rest = body(data);
+
+        rest.get(..self.metadata_length).unwrap_or(rest)
+    }
+
+    /// What follows the metadata section, the zip archive in compressed saves.
+    pub fn after_metadata<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        body(data).get(self.metadata_length..).unwrap_or_default()
+    }
+}
+
+/// An in-game date, `year.month.day`.
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct GameDate {
+    year: i16,
+    month: u8,
+    day: u8,
+}
+
+impl GameDate {
+    /// Binary saves count hours from the first day of year -5000, in years of 365 days. Values
+    /// that are not on a whole day or fall outside years 1 to 9999 are not taken for dates.
+    pub fn from_binary(value: i64) -> Option<Self> {
+        const DAYS_IN_MONTHS: [i64; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+        if value <= 0 || value % 24 != 0 {
+            return None;
+        }
+
+        let days = value / 24;
+        let year = days / 365 - 5000;
+        if !(1..=9999).contains(&year) {
+            return None;
+        }
+
+        let mut day = days % 365;
+        let mut month = 0;
+        while day >= DAYS_IN_MONTHS[month] {
+            day -= DAYS_IN_MONTHS[month];
+            month += 1;
+        }
+
+        Some(Self {
+            year: year as i16,
+            month: month as u8 + 1,
+            day: day as u8 + 1,
+        })
+    }
+}
+
+impl FromStr for GameDate {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '.');
+        let mut next = || parts.next().ok_or_else(|| invalid_data("Incomplete date"));
+
+        let year = next()?.parse().map_err(invalid_data)?;
+        let month = next()?.parse().map_err(invalid_data)?;
+        let day = next()?.parse().map_err(invalid_data)?;
+
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return Err(invalid_data(format!("Date out of range: {s}")));
+        }
+
+        Ok(Self { year, month, day })
+    }
+}
+
+impl Display for GameDate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.year, self.month, self.day)
+    }
+}
+
+/// What the `meta_data` block of a save tells about it. Binary metadata can only be read with a
+/// token table, see `TokenTable`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SaveMetadata {
+    pub player_name: Option<String>,
+    pub house_name: Option<String>,
+    pub date: Option<GameDate>,
+    pub game_version: Option<String>,
+    pub is_ironman: bool,
+    pub mods: Vec<String>,
+}
+
+impl SaveMetadata {
+    /// Reads the header and metadata of a whole save, compressed or not. Should the metadata
+    /// section hold no `meta_data` block, the one at the start of the gamestate is read instead.
+    pub fn parse(data: &[u8]) -> Result<Self, std::io::Error> {
+        let reader = SaveReader::open(data)?;
+
+        match reader.encoding() {
+            SaveEncoding::Text => reader
+                .metadata()
+                .and_then(|metadata| Self::from_tree(&text::parse(&metadata)?))
+                .or_else(|_| {
+                    let gamestate = reader.gamestate()?;
+                    Self::from_tree(&text::parse_entry(&gamestate, "meta_data")?)
+                }),
+            // Without a token table nothing but the ironman flag is known. The game only writes
+            // binary saves in ironman mode.
+            SaveEncoding::Binary => {
+                let metadata = reader.metadata()?;
+                let tree = binary::parse(&metadata, TokenTable::shared())?;
+                let root = tree.root();
+
+                Ok(Self {
+                    is_ironman: true,
+                    ..Self::from_entry(root.get("meta_data").unwrap_or(root))
+                })
+            }
+        }
+    }
+
+    /// Reads the metadata from a tree holding a `meta_data` block.
+    fn from_tree(tree: &Tree) -> Result<Self, std::io::Error> {
+        let metadata = tree
+            .root()
+            .get("meta_data")
+            .ok_or_else(|| invalid_data("No metadata in save"))?;
+
+        Ok(Self::from_entry(metadata))
+    }
+
+    /// Reads the fields this tool shows from the entries of `metadata`.
+    fn from_entry(metadata: Entry) -> Self {
+        let string = |key| {
+            let scalar = metadata.get(key)?.scalar()?;
+            Some(scalar.to_string_lossy().into_owned())
+        };
+
+        let mods = metadata.get("mods").map_or_else(Vec::new, |mods| {
+            mods.children()
+                .filter_map(|entry| entry.scalar())
+                .map(|scalar| scalar.to_string_lossy().into_owned())
+                .collect()
+        });
+
+        Self {
+            player_name: string("meta_player_name"),
+            house_name: string("meta_house_name"),
+            date: metadata
+                .get("meta_date")
+                .and_then(|entry| entry.scalar()?.as_date()),
+            game_version: string("version"),
+            is_ironman: metadata
+                .get("ironman")
+                .and_then(|entry| entry.scalar()?.as_bool())
+                .unwrap_or(false),
+            mods,
+        }
+    }
+
+    /// The player character and their house, as far as they are known.
+    pub fn ruler(&self) -> Option<String> {
+        match (&self.player_name, &self.house_name) {
+            (Some(player_name), Some(house_name)) => Some(format!("{player_name} of {house_name}")),
+            (Some(name), None) | (None, Some(name)) => Some(name.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Ruler, date and game version, leaving out whatever is unknown.
+impl Display for SaveMetadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(ruler) = self.ruler() {
+            parts.push(ruler);
+        }
+        if let Some(date) = self.date {
+            parts.push(date.to_string());
+        }
+        if let Some(game_version) = &self.game_version {
+            parts.push(format!("version {game_version}"));
+        }
+        if self.is_ironman {
+            parts.push("ironman".to_owned());
+        }
+        if !self.mods.is_empty() {
+            parts.push(format!("{} mods", self.mods.len()));
+        }
+
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Reads the whole gamestate of a save, compressed or not, into a tree that owns its data. The
+/// tree takes over the save, or the gamestate inflated from it, instead of holding a copy.
+pub fn parse_gamestate(mut data: Vec<u8>) -> Result<Tree<'static>, std::io::Error> {
+    let reader = SaveReader::open(&data)?;
+    let encoding = reader.encoding();
+    let inflated = match reader.gamestate()? {
+        Cow::Owned(gamestate) => Some(gamestate),
+        Cow::Borrowed(_) => None,
+    };
+    // An uncompressed gamestate is all of the save after the header, which is cut off in place.
+    let gamestate = inflated.unwrap_or_else(|| {
+        data.drain(..HEADER_LENGTH);
+        data
+    });
+
+    match encoding {
+        SaveEncoding::Text => Tree::parse_owned(gamestate, text::parse),
+        SaveEncoding::Binary => Tree::parse_owned(gamestate, |gamestate| {
+            binary::parse(gamestate, TokenTable::shared())
+        }),
+    }
+}
+
+/// Everything after the header.
+fn body(data: &[u8]) -> &[u8] {
+    data.get(HEADER_LENGTH..).unwrap_or_default()
+}
+
+fn parse_hex(digits: &[u8]) -> Result<usize, std::io::Error> {
+    let digits = std::str::from_utf8(digits).map_err(invalid_data)?;
+
+    usize::from_str_radix(digits, 16).map_err(invalid_data)
+}
+
+fn invalid_data<E>(error: E) -> std::io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const META_DATA: &str = "meta_data={
+\tmeta_player_name=\"Ragnar\"
+\tmeta_house_name=\"Lodbrok\"
+\tmeta_date=867.1.1
+\tversion=\"1.12.4\"
+\tironman=yes
+\tmods={ \"Mod A\" \"Mod B\" }
+}
+";
+
+    fn date(text: &str) -> GameDate {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn header_reads_kind_and_metadata_length() {
+        let header = SaveHeader::parse(b"SAV010306c5b64a0000d5d3\n").unwrap();
+
+        assert_eq!(header.encoding(), SaveEncoding::Binary);
+        assert!(header.is_compressed());
+        assert_eq!(header.metadata_length, 0xd5d3);
+
+        let header = SaveHeader::parse(b"SAV0100a1b2c3d400000010\nrest").unwrap();
+
+        assert_eq!(header.encoding(), SaveEncoding::Text);
+        assert!(!header.is_compressed());
+        assert_eq!(header.metadata_length, 0x10);
+    }
+
+    #[test]
+    fn header_splits_off_the_metadata() {
+        let data = b"SAV0102a1b2c3d400000004\nmetaPK\x03\x04";
+        let header = SaveHeader::parse(data).unwrap();
+
+        assert_eq!(header.metadata(data), b"meta");
+        assert_eq!(header.after_metadata(data), b"PK\x03\x04");
+    }
+
+    #[test]
+    fn header_rejects_other_files() {
+        for data in [
+            &b"SAV0100a1b2c3d400000010"[..],
+            b"PK\x03\x0400a1b2c3d400000010\n",
+            b"SAV0104a1b2c3d400000010\n",
+            b"SAV01zza1b2c3d400000010\n",
+            b"SAV0100a1b2c3d4000000zz\n",
+        ] {
+            assert!(SaveHeader::parse(data).is_err());
+        }
+    }
+
+    #[test]
+    fn dates_are_read_from_hours() {
+        let hours = |days: i64| days * 24;
+
+        assert_eq!(
+            GameDate::from_binary(hours(5867 * 365)),
+            Some(date("867.1.1"))
+        );
+        assert_eq!(
+            GameDate::from_binary(hours(6066 * 365 + 257)),
+            Some(date("1066.9.15"))
+        );
+        assert_eq!(
+            GameDate::from_binary(hours(5001 * 365 + 364)),
+            Some(date("1.12.31"))
+        );
+    }
+
+    #[test]
+    fn other_numbers_are_not_dates() {
+        assert_eq!(GameDate::from_binary(0), None);
+        assert_eq!(GameDate::from_binary(-24), None);
+        assert_eq!(GameDate::from_binary(5867 * 365 * 24 + 1), None);
+        assert_eq!(GameDate::from_binary(5000 * 365 * 24), None);
+        assert_eq!(GameDate::from_binary(15_000 * 365 * 24), None);
+        assert_eq!(GameDate::from_binary(1500), None);
+    }
+
+    #[test]
+    fn metadata_reads_the_shown_fields() {
+        let metadata = SaveMetadata::from_tree(&text::parse(META_DATA.as_bytes()).unwrap());
+
+        assert_eq!(
+            metadata.unwrap(),
+            SaveMetadata {
+                player_name: Some("Ragnar".to_owned()),
+                house_name: Some("Lodbrok".to_owned()),
+                date: Some(date("867.1.1")),
+                game_version: Some("1.12.4".to_owned()),
+                is_ironman: true,
+                mods: vec!["Mod A".to_owned(), "Mod B".to_owned()],
+            }
+        );
+    }
+
+    #[test]
+    fn metadata_leaves_out_missing_fields() {
+        let tree = text::parse(b"meta_data={ meta_player_name=Ragnar ironman=no }").unwrap();
+        let metadata = SaveMetadata::from_tree(&tree).unwrap();
+
+        assert_eq!(
+            metadata,
+            SaveMetadata {
+                player_name: Some("Ragnar".to_owned()),
+                ..SaveMetadata::default()
+            }
+        );
+        assert!(SaveMetadata::from_tree(&text::parse(b"date=867.1.1").unwrap()).is_err());
+    }
+
+    #[test]
+    fn gamestate_is_read_from_an_uncompressed_save() {
+        let data = format!(
+            "SAV0100a1b2c3d4{:08x}\n{META_DATA}date=867.1.2",
+            META_DATA.len()
+        );
+        let tree = parse_gamestate(data.into_bytes()).unwrap();
+
+        let played = tree
+            .root()
+            .get("date")
+            .and_then(|entry| entry.scalar()?.as_date());
+        assert_eq!(played, Some(date("867.1.2")));
+        assert_eq!(tree.root().children().count(), 2);
+    }
+
+    #[test]
+    fn gamestate_is_read_from_a_compressed_save() {
+        let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        archive
+            .start_file("gamestate", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut archive, b"date=867.1.2 list={ 1 2 }").unwrap();
+        let archive = archive.finish().unwrap().into_inner();
+
+        let data = [&b"SAV0102a1b2c3d400000000\n"[..], &archive].concat();
+        let tree = parse_gamestate(data).unwrap();
+
+        assert_eq!(format!("{:?}", tree.root()), "{date=867.1.2 list=[1 2]}");
+    }
+
+    #[test]
+    fn metadata_is_read_from_a_whole_save() {
+        let data = format!("SAV0100a1b2c3d4{:08x}\n{META_DATA}", META_DATA.len());
+        let metadata = SaveMetadata::parse(data.as_bytes()).unwrap();
+
+        assert_eq!(metadata.ruler().as_deref(), Some("Ragnar of Lodbrok"));
+        assert_eq!(metadata.date, Some(date("867.1.1")));
+        assert!(metadata.is_ironman);
+    }
+}
@@ -0,0 +1,178 @@
+use std::iter::Peekable;
+
+use crate::save_format::tree::{ScalarData, Tree, TreeBuilder};
+
+#[derive(Clone, Copy)]
+pub enum Token {
+    Equals,
+    Open,
+    Close,
+    Scalar(ScalarData),
+}
+
+/// Builds a tree from the tokens of either format, which share the same structure.
+pub struct Parser<'a, I: Iterator<Item = Token>> {
+    tokens: Peekable<I>,
+    builder: TreeBuilder<'a>,
+}
+
+impl<'a, I: Iterator<Item = Token>> Parser<'a, I> {
+    pub fn new(tokens: I, builder: TreeBuilder<'a>) -> Self {
+        Self {
+            tokens: tokens.peekable(),
+            builder,
+        }
+    }
+
+    /// Reads one scalar, `key=value` pair or block boundary. Returns `false` at the end of the
+    /// tokens.
+    pub fn step(&mut self) -> bool {
+        let Some(token) = self.tokens.next() else {
+            return false;
+        };
+
+        match token {
+            Token::Open => self.builder.open(None),
+            Token::Close => self.builder.close(),
+            // Left over from malformed text, there is nothing it could belong to.
+            Token::Equals => {}
+            Token::Scalar(key) if self.next_if(Token::Equals) => self.value(key),
+            // A block tagged with a name, like the colors in `rgb { 0 0 0 }`.
+            Token::Scalar(key) if key.is_name() && self.next_if(Token::Open) => {
+                self.builder.open(Some(key));
+            }
+            Token::Scalar(value) => self.builder.scalar(None, value),
+        }
+
+        true
+    }
+
+    /// Reads what follows the `=` after `key`.
+    pub fn value(&mut self, key: ScalarData) {
+        match self.tokens.next() {
+            Some(Token::Open) => self.builder.open(Some(key)),
+            Some(Token::Scalar(tag)) if tag.is_name() && self.next_if(Token::Open) => {
+                self.builder.open(Some(key));
+            }
+            Some(Token::Scalar(value)) => self.builder.scalar(Some(key), value),
+            Some(Token::Close) => self.builder.close(),
+            Some(Token::Equals) | None => {}
+        }
+    }
+
+    pub fn next_token(&mut self) -> Option<Token> {
+        self.tokens.next()
+    }
+
+    pub fn next_if(&mut self, expected: Token) -> bool {
+        self.tokens
+            .next_if(|token| std::mem::discriminant(token) == std::mem::discriminant(&expected))
+            .is_some()
+    }
+
+    /// Blocks currently open, not counting the root.
+    pub fn depth(&self) -> usize {
+        self.builder.depth()
+    }
+
+    pub fn parse(mut self) -> Tree<'a> {
+        while self.step() {}
+
+        self.finish()
+    }
+
+    pub fn finish(self) -> Tree<'a> {
+        self.builder.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_format::tree::Span;
+
+    /// Names are single letters of this source, picked by their offset.
+    const SOURCE: &[u8] = b"abcrgb";
+
+    fn name(start: usize, end: usize) -> Token {
+        Token::Scalar(ScalarData::Text(Span::new(start, end)))
+    }
+
+    fn parsed(tokens: Vec<Token>) -> String {
+        let builder = TreeBuilder::new(SOURCE, None).unwrap();
+        let tree = Parser::new(tokens.into_iter(), builder).parse();
+
+        format!("{:?}", tree.root())
+    }
+
+    #[test]
+    fn reads_tagged_values() {
+        let tokens = vec![
+            name(0, 1),
+            Token::Equals,
+            name(3, 6),
+            Token::Open,
+            Token::Scalar(ScalarData::Integer(1)),
+            Token::Close,
+        ];
+
+        assert_eq!(parsed(tokens), "{a=[1]}");
+    }
+
+    #[test]
+    fn quoted_strings_do_not_tag_blocks() {
+        let tokens = vec![
+            name(0, 1),
+            Token::Equals,
+            Token::Scalar(ScalarData::Quoted(Span::new(1, 2))),
+            Token::Open,
+            Token::Scalar(ScalarData::Integer(1)),
+            Token::Close,
+        ];
+
+        assert_eq!(parsed(tokens), "{a=b [1]}");
+    }
+
+    #[test]
+    fn ignores_stray_tokens() {
+        let tokens = vec![
+            Token::Close,
+            Token::Equals,
+            name(0, 1),
+            Token::Equals,
+            Token::Scalar(ScalarData::Integer(1)),
+            Token::Close,
+        ];
+
+        assert_eq!(parsed(tokens), "{a=1}");
+    }
+
+    #[test]
+    fn a_close_after_equals_ends_the_block() {
+        let tokens = vec![
+            name(0, 1),
+            Token::Equals,
+            Token::Open,
+            name(1, 2),
+            Token::Equals,
+            Token::Close,
+            name(2, 3),
+            Token::Equals,
+            Token::Scalar(ScalarData::Bool(true)),
+        ];
+
+        assert_eq!(parsed(tokens), "{a={} c=yes}");
+    }
+
+    #[test]
+    fn finish_closes_open_blocks() {
+        let tokens = vec![
+            name(0, 1),
+            Token::Equals,
+            Token::Open,
+            Token::Scalar(ScalarData::Integer(1)),
+        ];
+
+        assert_eq!(parsed(tokens), "{a=[1]}");
+    }
+}
@@ -0,0 +1,59 @@
+use std::borrow::Cow;
+use std::io::{Cursor, Read};
+
+use zip::ZipArchive;
+
+use crate::save_format::{SaveEncoding, SaveHeader, body, invalid_data};
+
+const GAMESTATE_ENTRY: &str = "gamestate";
+
+const METADATA_ENTRY: &str = "meta";
+
+/// Reads the parts of a save the same way whether it is compressed or not. Compressed entries are
+/// inflated in memory on every call.
+pub struct SaveReader<'a> {
+    header: SaveHeader,
+    data: &'a [u8],
+}
+
+impl<'a> SaveReader<'a> {
+    pub fn open(data: &'a [u8]) -> Result<Self, std::io::Error> {
+        Ok(Self {
+            header: SaveHeader::parse(data)?,
+            data,
+        })
+    }
+
+    pub const fn encoding(&self) -> SaveEncoding {
+        self.header.encoding()
+    }
+
+    /// The metadata section, or the `meta` entry of a compressed save without one.
+    pub fn metadata(&self) -> Result<Cow<'a, [u8]>, std::io::Error> {
+        let metadata = self.header.metadata(self.data);
+        if !metadata.is_empty() || !self.header.is_compressed() {
+            return Ok(Cow::Borrowed(metadata));
+        }
+
+        self.read_entry(METADATA_ENTRY).map(Cow::Owned)
+    }
+
+    pub fn gamestate(&self) -> Result<Cow<'a, [u8]>, std::io::Error> {
+        if !self.header.is_compressed() {
+            return Ok(Cow::Borrowed(body(self.data)));
+        }
+
+        self.read_entry(GAMESTATE_ENTRY).map(Cow::Owned)
+    }
+
+    fn read_entry(&self, name: &str) -> Result<Vec<u8>, std::io::Error> {
+        let archive = Cursor::new(self.header.after_metadata(self.data));
+        let mut archive = ZipArchive::new(archive).map_err(invalid_data)?;
+        let mut entry = archive.by_name(name).map_err(invalid_data)?;
+
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+
+        Ok(bytes)
+    }
+}
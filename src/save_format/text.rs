@@ -0,0 +1,190 @@
+use crate::save_format::parser::{Parser, Token};
+use crate::save_format::tree::{ScalarData, Span, Tree, TreeBuilder};
+
+/// Splits the text format into tokens as it goes, without copying anything.
+struct TextLexer<'a> {
+    text: &'a [u8],
+    position: usize,
+}
+
+impl<'a> TextLexer<'a> {
+    const fn new(text: &'a [u8]) -> Self {
+        Self { text, position: 0 }
+    }
+}
+
+impl Iterator for TextLexer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let byte = *self.text.get(self.position)?;
+            if byte == b'#' {
+                while self.text.get(self.position).is_some_and(|b| *b != b'\n') {
+                    self.position += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+
+        let start = self.position;
+        self.position += 1;
+
+        match self.text[start] {
+            b'=' => Some(Token::Equals),
+            b'{' => Some(Token::Open),
+            b'}' => Some(Token::Close),
+            b'"' => {
+                while let Some(byte) = self.text.get(self.position) {
+                    match byte {
+                        b'\\' => self.position += 2,
+                        b'"' => break,
+                        _ => self.position += 1,
+                    }
+                }
+
+                let end = self.position.min(self.text.len());
+                self.position = end + 1;
+
+                let span = Span::new(start + 1, end);
+
+                Some(Token::Scalar(ScalarData::Quoted(span)))
+            }
+            _ => {
+                while self
+                    .text
+                    .get(self.position)
+                    .is_some_and(|b| !b.is_ascii_whitespace() && !b"={}\"#".contains(b))
+                {
+                    self.position += 1;
+                }
+
+                let span = Span::new(start, self.position);
+
+                Some(Token::Scalar(ScalarData::Text(span)))
+            }
+        }
+    }
+}
+
+/// Parses a whole file in the text format. Malformed parts are read as well as they can be
+/// rather than failing the rest.
+pub fn parse(text: &[u8]) -> Result<Tree<'_>, std::io::Error> {
+    let builder = TreeBuilder::new(text, None)?;
+
+    Ok(Parser::new(TextLexer::new(text), builder).parse())
+}
+
+/// Parses only the first top-level `key=` entry, skipping what comes before it and leaving the
+/// rest unread. The tree holds that single entry, or nothing when it is not found.
+pub fn parse_entry<'a>(text: &'a [u8], key: &str) -> Result<Tree<'a>, std::io::Error> {
+    let builder = TreeBuilder::new(text, None)?;
+    let mut parser = Parser::new(TextLexer::new(text), builder);
+
+    let mut depth = 0_usize;
+    while let Some(token) = parser.next_token() {
+        match token {
+            Token::Open => depth += 1,
+            Token::Close => depth = depth.saturating_sub(1),
+            Token::Scalar(ScalarData::Text(span))
+                if depth == 0 && &text[span.range()] == key.as_bytes() =>
+            {
+                if parser.next_if(Token::Equals) {
+                    parser.value(ScalarData::Text(span));
+                    while parser.depth() > 0 && parser.step() {}
+                    break;
+                }
+            }
+            Token::Scalar(_) | Token::Equals => {}
+        }
+    }
+
+    Ok(parser.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(text: &str) -> String {
+        format!("{:?}", parse(text.as_bytes()).unwrap().root())
+    }
+
+    #[test]
+    fn reads_scalars_and_blocks() {
+        assert_eq!(
+            parsed("a=1 b=\"x y\" c={ d=2 e=no } list={ 1 2 3 }"),
+            "{a=1 b=x y c={d=2 e=no} list=[1 2 3]}"
+        );
+    }
+
+    #[test]
+    fn reads_tagged_blocks() {
+        assert_eq!(parsed("color=rgb { 10 20 30 }"), "{color=[10 20 30]}");
+        assert_eq!(parsed("hsv { 0.5 0.5 1 } a=1"), "{hsv=[0.5 0.5 1] a=1}");
+    }
+
+    #[test]
+    fn skips_comments() {
+        assert_eq!(parsed("a=1 # b=2 }\nc=3 #"), "{a=1 c=3}");
+    }
+
+    #[test]
+    fn ignores_stray_closes_and_equals() {
+        assert_eq!(parsed("} a=1 } = b=2"), "{a=1 b=2}");
+        assert_eq!(parsed("a= = b=2"), "{b=2}");
+    }
+
+    #[test]
+    fn closes_blocks_left_open() {
+        assert_eq!(parsed("a={ b={ c=1"), "{a={b={c=1}}}");
+    }
+
+    #[test]
+    fn unescapes_quotes() {
+        let tree = parse(br#"name="say \"hi\"" next=1"#).unwrap();
+        let name = tree
+            .root()
+            .get("name")
+            .and_then(|entry| entry.scalar())
+            .unwrap();
+
+        assert_eq!(name.to_string_lossy(), r#"say "hi""#);
+        assert!(tree.root().get("next").is_some());
+    }
+
+    #[test]
+    fn keeps_repeated_keys() {
+        let tree = parse(b"k=1 other=2 k=3").unwrap();
+        let root = tree.root();
+
+        let first = root.get("k").and_then(|entry| entry.scalar()).unwrap();
+        assert_eq!(first.to_string_lossy(), "1");
+        assert_eq!(format!("{root:?}"), "{k=1 other=2 k=3}");
+    }
+
+    #[test]
+    fn parse_entry_reads_only_the_top_level_entry() {
+        let text = b"before={ meta_data=0 } meta_data={ x=1 } after={ y=2 } meta_data=3";
+        let tree = parse_entry(text, "meta_data").unwrap();
+
+        assert_eq!(format!("{:?}", tree.root()), "{meta_data={x=1}}");
+    }
+
+    #[test]
+    fn parse_entry_stops_after_the_entry() {
+        let tree = parse_entry(b"meta_data={ x=1 } broken={ { {", "meta_data").unwrap();
+
+        assert_eq!(format!("{:?}", tree.root()), "{meta_data={x=1}}");
+    }
+
+    #[test]
+    fn parse_entry_without_the_entry_is_empty() {
+        let tree = parse_entry(b"a={ meta_data=1 }", "meta_data").unwrap();
+
+        assert_eq!(format!("{:?}", tree.root()), "{}");
+    }
+}
@@ -0,0 +1,368 @@
+use std::borrow::Cow;
+use std::fmt::{Debug, Formatter};
+
+use crate::save_format::binary::TokenTable;
+use crate::save_format::{GameDate, invalid_data};
+
+/// Byte range of a token in the parsed data. Offsets are kept in 32 bits, which is why larger
+/// inputs are refused.
+#[derive(Clone, Copy)]
+pub struct Span {
+    start: u32,
+    end: u32,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self {
+            start: start as u32,
+            end: end as u32,
+        }
+    }
+
+    pub const fn range(&self) -> std::ops::Range<usize> {
+        self.start as usize..self.end as usize
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum ScalarData {
+    Text(Span),
+    /// Without the quotes, escapes are left as they are.
+    Quoted(Span),
+    Integer(i64),
+    Unsigned(u64),
+    Float(f64),
+    Bool(bool),
+    /// A name from the binary format, looked up in the token table when it is read.
+    Token(u16),
+}
+
+impl ScalarData {
+    /// Whether this can name a tagged block, as quoted strings and numbers cannot.
+    pub const fn is_name(&self) -> bool {
+        matches!(self, Self::Text(_) | Self::Token(_))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum NodeValue {
+    Scalar(ScalarData),
+    Object,
+    Array,
+}
+
+struct Node {
+    key: Option<ScalarData>,
+    value: NodeValue,
+    /// Index after the last descendant.
+    end: u32,
+}
+
+/// Every key and value of a parsed file, stored flat in document order so that millions of
+/// nodes stay compact. A block is followed by its descendants, the next sibling comes after them.
+/// Text is not copied, scalars point into the parsed data.
+pub struct Tree<'a> {
+    source: Cow<'a, [u8]>,
+    names: Option<&'static TokenTable>,
+    nodes: Vec<Node>,
+}
+
+impl Tree<'_> {
+    /// The unnamed object holding the top-level entries.
+    pub fn root(&self) -> Entry<'_> {
+        self.entry(0)
+    }
+
+    /// The entry at `index` in document order, the root being the first.
+    pub fn entry(&self, index: usize) -> Entry<'_> {
+        Entry { tree: self, index }
+    }
+}
+
+impl Tree<'static> {
+    /// Parses `source` with `parse` and moves it into the tree afterwards, so the tree owns its
+    /// data without a copy of it.
+    pub fn parse_owned(
+        source: Vec<u8>,
+        parse: impl FnOnce(&[u8]) -> Result<Tree<'_>, std::io::Error>,
+    ) -> Result<Self, std::io::Error> {
+        let (names, nodes) = {
+            let tree = parse(&source)?;
+            (tree.names, tree.nodes)
+        };
+
+        Ok(Self {
+            source: Cow::Owned(source),
+            names,
+            nodes,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Entry<'t> {
+    tree: &'t Tree<'t>,
+    index: usize,
+}
+
+impl<'t> Entry<'t> {
+    fn node(&self) -> &'t Node {
+        &self.tree.nodes[self.index]
+    }
+
+    fn scalar_of(&self, data: ScalarData) -> Scalar<'t> {
+        Scalar {
+            source: &self.tree.source,
+            names: self.tree.names,
+            data,
+        }
+    }
+
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn key(&self) -> Option<Scalar<'t>> {
+        self.node().key.map(|data| self.scalar_of(data))
+    }
+
+    /// `None` for objects and arrays.
+    pub fn scalar(&self) -> Option<Scalar<'t>> {
+        match self.node().value {
+            NodeValue::Scalar(data) => Some(self.scalar_of(data)),
+            NodeValue::Object | NodeValue::Array => None,
+        }
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self.node().value, NodeValue::Array)
+    }
+
+    /// Entries of an object or the items of an array, in the order they were written.
+    pub fn children(&self) -> Children<'t> {
+        Children {
+            tree: self.tree,
+            next: self.index + 1,
+            end: self.node().end as usize,
+        }
+    }
+
+    /// The first child under `key`. Keys may repeat, the rest are found through `children`.
+    pub fn get(&self, key: &str) -> Option<Self> {
+        self.children()
+            .find(|child| child.key().and_then(Scalar::as_bytes) == Some(key.as_bytes()))
+    }
+}
+
+/// On one line in a form close to the text format. Quotes are left out, arrays are written in
+/// brackets.
+impl Debug for Entry<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(key) = self.key() {
+            write!(f, "{}=", key.to_string_lossy())?;
+        }
+
+        if let Some(scalar) = self.scalar() {
+            return write!(f, "{}", scalar.to_string_lossy());
+        }
+
+        let (open, close) = if self.is_array() {
+            ("[", "]")
+        } else {
+            ("{", "}")
+        };
+        write!(f, "{open}")?;
+        for (position, child) in self.children().enumerate() {
+            if position > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{child:?}")?;
+        }
+        write!(f, "{close}")
+    }
+}
+
+pub struct Children<'t> {
+    tree: &'t Tree<'t>,
+    next: usize,
+    end: usize,
+}
+
+impl<'t> Iterator for Children<'t> {
+    type Item = Entry<'t>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.end {
+            return None;
+        }
+
+        let entry = Entry {
+            tree: self.tree,
+            index: self.next,
+        };
+        self.next = entry.node().end as usize;
+
+        Some(entry)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Scalar<'a> {
+    source: &'a [u8],
+    names: Option<&'static TokenTable>,
+    data: ScalarData,
+}
+
+impl<'a> Scalar<'a> {
+    /// The text of strings and of tokens with a known name, `None` for anything else.
+    pub fn as_bytes(self) -> Option<&'a [u8]> {
+        match self.data {
+            ScalarData::Text(span) | ScalarData::Quoted(span) => Some(&self.source[span.range()]),
+            ScalarData::Token(id) => self.names?.name_of(id).map(str::as_bytes),
+            ScalarData::Integer(_)
+            | ScalarData::Unsigned(_)
+            | ScalarData::Float(_)
+            | ScalarData::Bool(_) => None,
+        }
+    }
+
+    /// Quoted strings have their escapes resolved, tokens without a known name show their id.
+    pub fn to_string_lossy(self) -> Cow<'a, str> {
+        let text = match self.data {
+            ScalarData::Integer(value) => return Cow::Owned(value.to_string()),
+            ScalarData::Unsigned(value) => return Cow::Owned(value.to_string()),
+            ScalarData::Float(value) => return Cow::Owned(value.to_string()),
+            ScalarData::Bool(value) => return Cow::Borrowed(if value { "yes" } else { "no" }),
+            ScalarData::Token(id) => match self.as_bytes() {
+                Some(name) => String::from_utf8_lossy(name),
+                None => Cow::Owned(format!("0x{id:04x}")),
+            },
+            ScalarData::Text(span) | ScalarData::Quoted(span) => {
+                String::from_utf8_lossy(&self.source[span.range()])
+            }
+        };
+        if !matches!(self.data, ScalarData::Quoted(_)) || !text.contains('\\') {
+            return text;
+        }
+
+        let mut unescaped = String::with_capacity(text.len());
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => unescaped.extend(chars.next()),
+                c => unescaped.push(c),
+            }
+        }
+
+        Cow::Owned(unescaped)
+    }
+
+    pub fn as_bool(self) -> Option<bool> {
+        if let ScalarData::Bool(value) = self.data {
+            return Some(value);
+        }
+
+        match self.as_bytes()? {
+            b"yes" => Some(true),
+            b"no" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Binary saves write dates as integers, which are told apart from other numbers by falling
+    /// on a plausible day.
+    pub fn as_date(self) -> Option<GameDate> {
+        match self.data {
+            ScalarData::Integer(value) => GameDate::from_binary(value),
+            ScalarData::Unsigned(value) => GameDate::from_binary(i64::try_from(value).ok()?),
+            _ => std::str::from_utf8(self.as_bytes()?).ok()?.parse().ok(),
+        }
+    }
+}
+
+/// Collects nodes as a parser reads them. Blocks left open at the end of the data are closed,
+/// and closing more blocks than were opened is ignored, as the game itself does.
+pub struct TreeBuilder<'a> {
+    tree: Tree<'a>,
+    open: Vec<usize>,
+}
+
+impl<'a> TreeBuilder<'a> {
+    /// Tokens are named from `names`, when there is one.
+    pub fn new(
+        source: &'a [u8],
+        names: Option<&'static TokenTable>,
+    ) -> Result<Self, std::io::Error> {
+        if u32::try_from(source.len()).is_err() {
+            return Err(invalid_data("Save is too large to parse"));
+        }
+
+        let root = Node {
+            key: None,
+            value: NodeValue::Object,
+            end: 0,
+        };
+
+        Ok(Self {
+            tree: Tree {
+                source: Cow::Borrowed(source),
+                names,
+                nodes: vec![root],
+            },
+            open: vec![0],
+        })
+    }
+
+    /// Blocks currently open, not counting the root.
+    pub fn depth(&self) -> usize {
+        self.open.len() - 1
+    }
+
+    pub fn scalar(&mut self, key: Option<ScalarData>, value: ScalarData) {
+        self.push(key, NodeValue::Scalar(value));
+    }
+
+    pub fn open(&mut self, key: Option<ScalarData>) {
+        self.open.push(self.tree.nodes.len());
+        self.push(key, NodeValue::Object);
+    }
+
+    /// A block whose children all lack keys is an array, any other one an object.
+    pub fn close(&mut self) {
+        if self.depth() == 0 {
+            return;
+        }
+        let Some(index) = self.open.pop() else {
+            return;
+        };
+
+        let end = self.tree.nodes.len();
+        let mut is_array = end > index + 1;
+        let mut child = index + 1;
+        while is_array && child < end {
+            is_array = self.tree.nodes[child].key.is_none();
+            child = self.tree.nodes[child].end as usize;
+        }
+
+        let node = &mut self.tree.nodes[index];
+        node.end = end as u32;
+        if is_array {
+            node.value = NodeValue::Array;
+        }
+    }
+
+    pub fn finish(mut self) -> Tree<'a> {
+        while self.depth() > 0 {
+            self.close();
+        }
+        self.tree.nodes[0].end = self.tree.nodes.len() as u32;
+
+        self.tree
+    }
+
+    fn push(&mut self, key: Option<ScalarData>, value: NodeValue) {
+        let end = self.tree.nodes.len() as u32 + 1;
+        self.tree.nodes.push(Node { key, value, end });
+    }
+}
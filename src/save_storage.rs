@@ -1,55 +1,152 @@
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::ptr::addr_of;
 use std::sync::mpsc;
 use std::sync::mpsc::Receiver;
 use std::time::{Duration, SystemTime};
 
+use chrono::{DateTime, Local};
+use log::{Level, log};
+
 use crate::CHANNEL;
-use crate::path::save_data;
+use crate::annotation::Annotation;
+use crate::file_op::{gather_file_data, write_atomically, write_new_verified, write_verified};
+use crate::metadata_backfill::{MetadataBackfill, backfill_metadata};
+use crate::path::export_directory;
+use crate::retention::RetentionPolicy;
 use crate::save_file::SaveFile;
 use crate::save_file_watcher::SaveFileUpdate;
 use crate::save_version::SaveVersion;
 use crate::storage::Storage;
+use crate::storage_recovery::StorageRecovery;
 use crate::time_budget::TimeBudget;
+use crate::ui::notification::{NotificationLevel, push};
+use crate::verification::{Verification, verify};
+use crate::version_entry::VersionKey;
 
 pub struct SaveStorage {
     storage: Storage,
     receiver: &'static Receiver<SaveFileUpdate>,
-    ignore_list: HashSet<PathBuf>,
+    ignore_list: HashMap<PathBuf, SystemTime>,
+    verification: Option<Verification>,
+    metadata_backfill: Option<MetadataBackfill>,
+    retention: RetentionPolicy,
 }
 
 impl SaveStorage {
-    pub fn new() -> Self {
-        Self {
-            receiver: &unsafe { CHANNEL.get_or_init(mpsc::channel) }.1,
-            storage: Storage::read_saves(),
-            ignore_list: HashSet::default(),
-        }
+    pub fn new(retention: RetentionPolicy) -> Result<Self, std::io::Error> {
+        let storage = Storage::read_saves()?;
+
+        let undescribed = storage.undescribed();
+        let metadata_backfill = (!undescribed.is_empty()).then(|| backfill_metadata(undescribed));
+
+        Ok(Self {
+            receiver: &unsafe { &*addr_of!(CHANNEL) }.get_or_init(mpsc::channel).1,
+            storage,
+            ignore_list: HashMap::default(),
+            verification: None,
+            metadata_backfill,
+            retention,
+        })
     }
 
     pub fn update(&mut self) {
         let time_budget = TimeBudget::new(Duration::from_millis(1));
 
-        let mut any_updated = false;
+        let mut is_updated = false;
         while let Ok(update) = self.receiver.try_recv() {
-            if self.ignore_list.remove(&update.0) {
+            if self.ignore_list.get(&update.0) == Some(&update.1) {
+                log!(
+                    Level::Debug,
+                    "Skipped {}, it was written by a restore",
+                    update.0.display()
+                );
                 continue;
             }
 
-            any_updated = true;
-
-            self.storage.apply_update(update);
+            let (path, time) = (update.0.clone(), update.1);
+            match self.storage.apply_update(update) {
+                Ok(()) => {
+                    match self.storage.metadata_of(&path, &time) {
+                        Some(metadata) => log!(
+                            Level::Info,
+                            "Stored a new version of {}: {metadata}",
+                            path.display()
+                        ),
+                        None => log!(
+                            Level::Warn,
+                            "Stored a new version of {} without readable metadata",
+                            path.display()
+                        ),
+                    }
+                    is_updated = true;
+                }
+                Err(e) => push(
+                    NotificationLevel::Error,
+                    format!("New version of {} was not stored: {e}", path.display()),
+                ),
+            }
 
             if time_budget.is_expired() {
                 break;
             }
         }
 
-        if any_updated {
-            let _ = self.write_to_file();
+        if is_updated {
+            if let Err(e) = self.storage.prune(&self.retention) {
+                push(
+                    NotificationLevel::Warning,
+                    format!("Old versions were not pruned: {e}"),
+                );
+            }
+        }
+
+        if self
+            .metadata_backfill
+            .as_ref()
+            .is_some_and(MetadataBackfill::is_finished)
+        {
+            self.finish_metadata_backfill();
+        }
+
+        if let Err(e) = self.storage.compact_if_needed() {
+            push(
+                NotificationLevel::Error,
+                format!("Version index checkpoint was not written: {e}"),
+            );
         }
     }
 
+    fn finish_metadata_backfill(&mut self) {
+        let Some(metadata_backfill) = self.metadata_backfill.take() else {
+            return;
+        };
+
+        let described = match metadata_backfill.join() {
+            Ok(described) => described,
+            Err(e) => {
+                push(
+                    NotificationLevel::Warning,
+                    format!("Metadata of earlier versions was not read: {e}"),
+                );
+                return;
+            }
+        };
+
+        let count = described.len();
+        for ((path, time), metadata) in described {
+            if let Err(e) = self.storage.describe(path, time, metadata) {
+                push(
+                    NotificationLevel::Error,
+                    format!("Metadata of earlier versions was not stored: {e}"),
+                );
+                return;
+            }
+        }
+        log!(Level::Info, "Read the metadata of {count} earlier versions");
+    }
+
     pub fn save_files(&self) -> impl Iterator<Item = SaveFile> + '_ {
         let mut files = self
             .storage
@@ -71,13 +168,26 @@ impl SaveStorage {
         files.into_iter().rev()
     }
 
-    pub fn save_versions(&self, file_path: &PathBuf) -> impl Iterator<Item = SaveVersion> + '_ {
+    pub fn save_versions(&self, file_path: &PathBuf) -> impl Iterator<Item = SaveVersion<'_>> + '_ {
         let mut versions = self
             .storage
             .get(file_path)
             .into_iter()
             .flatten()
-            .map(|(k, _)| SaveVersion::new(*k))
+            .map(|(time, entry)| {
+                let label = self
+                    .storage
+                    .annotation_of(file_path, time)
+                    .map_or("", |annotation| annotation.label.as_str());
+
+                SaveVersion::new(
+                    *time,
+                    entry.size(),
+                    self.storage.is_pinned(file_path, time),
+                    label,
+                    self.storage.metadata_of(file_path, time),
+                )
+            })
             .collect::<Vec<SaveVersion>>();
 
         versions.sort();
@@ -85,21 +195,209 @@ impl SaveStorage {
         versions.into_iter().rev()
     }
 
-    pub fn data_of(&self, path: &PathBuf, time: &SystemTime) -> Option<&[u8]> {
-        self.storage.get(path)?.get(time).map(Vec::as_slice)
+    pub fn data_of(&self, path: &PathBuf, time: &SystemTime) -> Result<Vec<u8>, std::io::Error> {
+        self.storage.data_of(path, time)
     }
 
-    pub fn add_ignore_record(&mut self, path: PathBuf) {
-        self.ignore_list.insert(path);
+    pub fn is_pinned(&self, path: &Path, time: &SystemTime) -> bool {
+        self.storage.is_pinned(path, time)
+    }
+
+    pub fn set_pinned(
+        &mut self,
+        versions: &[VersionKey],
+        pinned: bool,
+    ) -> Result<(), std::io::Error> {
+        versions
+            .iter()
+            .try_for_each(|(path, time)| self.storage.set_pinned(path.clone(), *time, pinned))
+    }
+
+    /// Captures the file on disk before writing the version over it, so the restore can be undone
+    /// even when the watcher missed the last change to the file.
+    pub fn restore(&mut self, path: &PathBuf, time: &SystemTime) -> Result<(), std::io::Error> {
+        let data = self.data_of(path, time)?;
+        let previous_time = self.capture(path)?;
+
+        let written_time = write_verified(path, &data)?;
+        self.add_ignore_record(path.clone(), written_time);
+
+        // Without a file to go back to, an older undo point would put back what was there before
+        // an earlier restore instead, so it is cleared.
+        self.storage.set_undo_point(path.clone(), previous_time)
+    }
+
+    /// Puts back what was on disk before the last restore of the file. Whatever is on disk now is
+    /// captured first, in case the game saved over the restored version since.
+    pub fn undo_restore(&mut self, path: &PathBuf) -> Result<(), std::io::Error> {
+        let time = self.storage.undo_point_of(path).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "There is no restore to undo")
+        })?;
+
+        let data = self.data_of(path, &time)?;
+        self.capture(path)?;
+
+        let written_time = write_verified(path, &data)?;
+        self.add_ignore_record(path.clone(), written_time);
+
+        self.storage.set_undo_point(path.clone(), None)
+    }
+
+    pub fn can_undo_restore(&self, path: &Path) -> bool {
+        self.storage.undo_point_of(path).is_some()
+    }
+
+    /// Stores the file as it is on disk unless it was stored already. Returns the time it is
+    /// stored under, or `None` when there is no such file.
+    fn capture(&mut self, path: &PathBuf) -> Result<Option<SystemTime>, std::io::Error> {
+        let update = match gather_file_data(path) {
+            Ok(update) => update,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let time = update.1;
+        let is_captured = self
+            .storage
+            .get(path)
+            .is_some_and(|versions| versions.contains_key(&time));
+        if !is_captured {
+            self.storage.apply_update(update)?;
+        }
+
+        Ok(Some(time))
     }
 
-    pub fn write_to_file(&self) -> Result<(), std::io::Error> {
-        let bytes = postcard::to_stdvec(&self.storage)
-            .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
-        let saves_path =
-            save_data().map_err(|_| std::io::Error::from(std::io::ErrorKind::NotFound))?;
-        std::fs::write(saves_path, bytes)?;
+    /// Writes a version to a new file next to the one it was captured from, which starts a history
+    /// of its own. An existing file is never overwritten. Returns the path of the new file.
+    pub fn restore_as(
+        &mut self,
+        path: &PathBuf,
+        time: &SystemTime,
+        file_name: &str,
+    ) -> Result<PathBuf, std::io::Error> {
+        let mut file_name = file_name.to_owned();
+        if !file_name.ends_with(".ck3") {
+            file_name.push_str(".ck3");
+        }
+
+        if Path::new(&file_name).file_name() != Some(OsStr::new(&file_name)) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("'{file_name}' is not a valid file name"),
+            ));
+        }
+
+        let new_path = path.with_file_name(file_name);
+        if new_path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} already exists", new_path.display()),
+            ));
+        }
+
+        let data = self.data_of(path, time)?;
+        let written_time = write_new_verified(&new_path, &data)?;
+        self.add_ignore_record(new_path.clone(), written_time);
+        self.capture(&new_path)?;
+
+        Ok(new_path)
+    }
+
+    /// Writes each version to the export directory, named after its file and the time it was
+    /// captured. Returns the export directory.
+    pub fn export(&self, versions: &[VersionKey]) -> Result<PathBuf, std::io::Error> {
+        let directory = export_directory()?;
+        std::fs::create_dir_all(&directory)?;
+
+        for (path, time) in versions {
+            let data = self.data_of(path, time)?;
+            let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let time = DateTime::<Local>::from(*time).format("%Y-%m-%d %H-%M-%S");
+
+            write_atomically(&directory.join(format!("{file_stem} {time}.ck3")), &data)?;
+        }
+
+        Ok(directory)
+    }
+
+    pub fn annotation_of(&self, path: &Path, time: &SystemTime) -> Annotation {
+        self.storage
+            .annotation_of(path, time)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn annotate(
+        &mut self,
+        path: &Path,
+        time: &SystemTime,
+        annotation: Annotation,
+    ) -> Result<(), std::io::Error> {
+        self.storage.annotate(path.to_path_buf(), *time, annotation)
+    }
+
+    /// Pinned versions are kept. Returns how many of the versions were deleted.
+    pub fn delete(&mut self, versions: Vec<VersionKey>) -> Result<usize, std::io::Error> {
+        self.storage.delete(versions)
+    }
+
+    pub fn start_verification(&mut self) {
+        if self.verification.is_some() {
+            return;
+        }
+
+        let versions = self
+            .storage
+            .iter()
+            .flat_map(|(path, versions)| {
+                versions
+                    .iter()
+                    .map(|(time, entry)| ((path.clone(), *time), *entry))
+            })
+            .collect();
+
+        self.verification = Some(verify(versions));
+    }
+
+    pub const fn is_verifying(&self) -> bool {
+        self.verification.is_some()
+    }
+
+    /// Returns the corrupted versions once the verification started last has finished. Versions
+    /// removed in the meantime are left out, as their data may have been deleted while they were
+    /// being read.
+    pub fn take_verification_result(&mut self) -> Option<Result<Vec<VersionKey>, std::io::Error>> {
+        if !self.verification.as_ref()?.is_finished() {
+            return None;
+        }
+
+        let result = self.verification.take().map(Verification::join)?;
+
+        Some(result.map(|corrupted| {
+            corrupted
+                .into_iter()
+                .filter(|(path, time)| {
+                    self.storage
+                        .get(path)
+                        .is_some_and(|versions| versions.contains_key(time))
+                })
+                .collect()
+        }))
+    }
+
+    pub fn quarantine(&mut self, versions: &[VersionKey]) -> Result<(), std::io::Error> {
+        versions
+            .iter()
+            .try_for_each(|(path, time)| self.storage.quarantine(path.clone(), *time))
+    }
+
+    pub fn take_recovery(&mut self) -> Option<StorageRecovery> {
+        self.storage.take_recovery()
+    }
 
-        Ok(())
+    /// Updates of the file are ignored for as long as it stays as written at `time`.
+    pub fn add_ignore_record(&mut self, path: PathBuf, time: SystemTime) {
+        self.ignore_list.insert(path, time);
     }
 }
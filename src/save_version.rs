@@ -1,28 +1,54 @@
 use std::cmp::Ordering;
 use std::time::SystemTime;
 
+use crate::save_format::SaveMetadata;
+
+/// A row of the version list. The label and metadata are borrowed from the history, as the rows
+/// are built again on every frame.
 #[derive(Eq, PartialEq)]
-pub struct SaveVersion(SystemTime);
+pub struct SaveVersion<'a>(SystemTime, u64, bool, &'a str, Option<&'a SaveMetadata>);
 
-impl SaveVersion {
-    pub const fn new(time: SystemTime) -> Self {
-        Self(time)
+impl<'a> SaveVersion<'a> {
+    pub const fn new(
+        time: SystemTime,
+        size: u64,
+        is_pinned: bool,
+        label: &'a str,
+        metadata: Option<&'a SaveMetadata>,
+    ) -> Self {
+        Self(time, size, is_pinned, label, metadata)
     }
 
     pub const fn time(&self) -> &SystemTime {
         &self.0
     }
+
+    pub const fn size(&self) -> u64 {
+        self.1
+    }
+
+    pub const fn is_pinned(&self) -> bool {
+        self.2
+    }
+
+    pub const fn label(&self) -> &'a str {
+        self.3
+    }
+
+    /// `None` for versions whose metadata has not been read yet.
+    pub const fn metadata(&self) -> Option<&'a SaveMetadata> {
+        self.4
+    }
 }
 
-impl PartialOrd for SaveVersion {
+impl PartialOrd for SaveVersion<'_> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.0.cmp(&other.0))
+        Some(self.cmp(other))
     }
 }
 
-impl Ord for SaveVersion {
+impl Ord for SaveVersion<'_> {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other)
-            .expect("Under no circumstances SaveVersion::cmp should throw")
+        self.0.cmp(&other.0)
     }
 }
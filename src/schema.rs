@@ -0,0 +1,40 @@
+/// Every checkpoint and journal file starts with this magic followed by the format version as a
+/// little-endian `u16`.
+const MAGIC: [u8; 4] = *b"CK3S";
+
+pub const FORMAT_VERSION: u16 = 8;
+
+pub const HEADER_SIZE: usize = MAGIC.len() + 2;
+
+pub fn header(version: u16) -> [u8; HEADER_SIZE] {
+    let mut header = [0; HEADER_SIZE];
+    header[..MAGIC.len()].copy_from_slice(&MAGIC);
+    header[MAGIC.len()..].copy_from_slice(&version.to_le_bytes());
+
+    header
+}
+
+pub fn with_header(version: u16, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + payload.len());
+    bytes.extend(header(version));
+    bytes.extend(payload);
+
+    bytes
+}
+
+/// Returns the format version and the rest of the file, or `None` for files written before
+/// headers were introduced.
+pub fn read_header(bytes: &[u8]) -> Option<(u16, &[u8])> {
+    let rest = bytes.strip_prefix(&MAGIC)?;
+    let version = u16::from_le_bytes(rest.get(..2)?.try_into().ok()?);
+
+    Some((version, &rest[2..]))
+}
+
+/// Strips the header of a file in the current format.
+pub fn payload_of(bytes: &[u8]) -> Result<&[u8], std::io::Error> {
+    match read_header(bytes) {
+        Some((FORMAT_VERSION, payload)) => Ok(payload),
+        _ => Err(std::io::Error::from(std::io::ErrorKind::InvalidData)),
+    }
+}
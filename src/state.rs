@@ -1,6 +1,20 @@
+use std::path::PathBuf;
+
+use crate::version_entry::VersionKey;
+
 #[derive(Clone, Eq, PartialEq)]
 pub enum State {
     MainMenu(usize, bool),
-    SaveFileSelected(usize, usize, bool),
+    SaveFileSelected(usize, usize),
+    Restore(usize, usize, VersionKey),
+    DeleteFile(usize, PathBuf),
+    DeleteVersion(usize, usize, Vec<VersionKey>),
+    EditLabel(usize, usize, VersionKey),
+    EditNote(usize, usize, VersionKey),
+    RestoreAs(usize, usize, VersionKey),
+    CorruptedVersions(usize),
+    Notifications(usize),
+    Logs(usize),
+    Gamestate(usize, usize, usize),
     Exit,
 }
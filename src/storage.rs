@@ -1,54 +1,528 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
-use crate::file_op::gather_file_data;
-use crate::path::{save_data, save_directory};
+use crate::annotation::Annotation;
+use crate::background_task::BackgroundTask;
+use crate::checkpoint::Checkpoint;
+use crate::file_op::{gather_file_data, is_temporary};
+use crate::journal::{Journal, JournalRecord};
+use crate::migration::{migrate, read_legacy_save_data};
+use crate::path::{
+    backup_index_file, damaged_index_file, index_file, legacy_save_data, save_directory,
+};
+use crate::retention::RetentionPolicy;
 use crate::save_file_watcher::SaveFileUpdate;
+use crate::save_format::SaveMetadata;
+use crate::storage_recovery::StorageRecovery;
+use crate::version_entry::{VersionEntry, VersionKey};
+use crate::version_store::{Usage, VersionStore};
 
-pub type InnerType = HashMap<PathBuf, HashMap<SystemTime, Vec<u8>>>;
+pub type InnerType = HashMap<PathBuf, HashMap<SystemTime, VersionEntry>>;
 
-#[derive(serde::Serialize, serde::Deserialize)]
-pub struct Storage(InnerType);
+const COMPACTION_THRESHOLD: usize = 64;
+
+pub struct Storage {
+    index: InnerType,
+    quarantine: InnerType,
+    pinned: HashSet<VersionKey>,
+    annotations: HashMap<VersionKey, Annotation>,
+    undo_points: HashMap<PathBuf, SystemTime>,
+    metadata: HashMap<VersionKey, SaveMetadata>,
+    versions: VersionStore,
+    journal: Journal,
+    checkpoint_generation: u64,
+    compaction: Option<BackgroundTask<u64>>,
+    recovery: Option<StorageRecovery>,
+}
 
 impl Storage {
-    fn empty() -> Self {
-        Self(InnerType::new())
-    }
+    pub fn read_saves() -> Result<Self, std::io::Error> {
+        migrate()?;
+
+        let versions = VersionStore::open()?;
+
+        let (checkpoint, mut recovery) = Self::read_latest_checkpoint()?;
+        let is_first_run = checkpoint.is_none() && recovery.is_none();
+        let checkpoint = checkpoint.unwrap_or_default();
+
+        let generations = Journal::generations_from(checkpoint.journal_generation)?;
+        let last_generation = generations
+            .last()
+            .copied()
+            .unwrap_or(checkpoint.journal_generation);
 
-    pub fn read_saves() -> Self {
-        Self::read_saves_from_serialized()
-            .or_else(|_| Self::read_saves_from_files())
-            .unwrap_or_else(|_| Self::empty())
+        let mut records = Vec::new();
+        for generation in generations {
+            let replay = Journal::replay(generation)?;
+            if replay.is_damaged {
+                recovery = recovery.or(Some(StorageRecovery::FromDamagedJournal));
+            }
+            records.extend(replay.records);
+        }
+
+        let mut storage = Self {
+            index: checkpoint.index,
+            quarantine: checkpoint.quarantine,
+            pinned: checkpoint.pinned,
+            annotations: checkpoint.annotations,
+            undo_points: checkpoint.undo_points,
+            metadata: checkpoint.metadata,
+            versions,
+            journal: Journal::open(last_generation, records.len())?,
+            checkpoint_generation: checkpoint.journal_generation,
+            compaction: None,
+            recovery,
+        };
+
+        records
+            .into_iter()
+            .for_each(|record| storage.apply_record(record));
+
+        // Whatever could be replayed is kept, and the save files currently on disk are captured
+        // on top of it when the history may be incomplete.
+        match storage.recovery {
+            None if !is_first_run => return Ok(storage),
+            None
+            | Some(
+                StorageRecovery::FromBackup
+                | StorageRecovery::FromDamagedJournal
+                | StorageRecovery::LegacyUnreadable,
+            ) => {
+                let _ = storage.read_saves_from_files();
+            }
+            Some(StorageRecovery::FromSaveFiles) => {
+                let _ = storage.read_saves_from_legacy();
+                let _ = storage.read_saves_from_files();
+            }
+        }
+
+        storage.compact()?;
+        storage.finish_compaction()?;
+
+        Ok(storage)
     }
 
-    fn read_saves_from_serialized() -> Result<Self, std::io::Error> {
-        let saves_path =
-            save_data().map_err(|_| std::io::Error::from(std::io::ErrorKind::NotFound))?;
-        let bytes = std::fs::read(saves_path)?;
+    /// A damaged checkpoint is kept aside for inspection and the previous generation is used in
+    /// its place. Neither existing means this is the first run, unless a legacy blob is left
+    /// behind because it could not be imported.
+    fn read_latest_checkpoint()
+    -> Result<(Option<Checkpoint>, Option<StorageRecovery>), std::io::Error> {
+        let index_path = index_file()?;
+        let backup_path = backup_index_file()?;
 
-        postcard::from_bytes(&bytes)
-            .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))
+        match Checkpoint::read(&index_path) {
+            Ok(checkpoint) => return Ok((Some(checkpoint), None)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !backup_path.exists() => {
+                let recovery = legacy_save_data()?
+                    .exists()
+                    .then_some(StorageRecovery::LegacyUnreadable);
+                return Ok((None, recovery));
+            }
+            Err(_) => {}
+        }
+
+        if index_path.exists() {
+            std::fs::rename(&index_path, damaged_index_file()?)?;
+        }
+
+        match Checkpoint::read(&backup_path) {
+            Ok(checkpoint) => Ok((Some(checkpoint), Some(StorageRecovery::FromBackup))),
+            Err(_) => Ok((None, Some(StorageRecovery::FromSaveFiles))),
+        }
     }
 
-    fn read_saves_from_files() -> Result<Self, std::io::Error> {
-        let mut storage = Self::empty();
+    fn read_saves_from_legacy(&mut self) -> Result<(), std::io::Error> {
+        read_legacy_save_data()?
+            .into_iter()
+            .flat_map(|(path, versions)| {
+                versions
+                    .into_iter()
+                    .map(move |(time, data)| (path.clone(), time, data))
+            })
+            .try_for_each(|update| self.apply_update(update))
+    }
 
+    fn read_saves_from_files(&mut self) -> Result<(), std::io::Error> {
         let path = save_directory()?;
         std::fs::read_dir(path)?
             .flatten()
             .map(|e| e.path())
+            .filter(|path| !is_temporary(path))
             .flat_map(|path| gather_file_data(&path))
-            .for_each(|update| storage.apply_update(update));
+            .for_each(|update| {
+                let _ = self.apply_update(update);
+            });
 
-        Ok(storage)
+        Ok(())
     }
 
-    pub fn apply_update(&mut self, save_file_update: SaveFileUpdate) {
+    /// Stores a new version along with its metadata. A version whose metadata cannot be read is
+    /// stored all the same.
+    pub fn apply_update(&mut self, save_file_update: SaveFileUpdate) -> Result<(), std::io::Error> {
         let (path, time, data) = save_file_update;
 
-        self.0.entry(path).or_default().insert(time, data);
+        let entry = self.versions.insert(&data)?;
+        let mut records = vec![JournalRecord::Add(path.clone(), time, entry)];
+        if let Ok(metadata) = SaveMetadata::parse(&data) {
+            records.push(JournalRecord::Describe(path, time, metadata));
+        }
+
+        for record in records {
+            self.journal.append(&record)?;
+            self.apply_record(record);
+        }
+
+        Ok(())
+    }
+
+    fn apply_record(&mut self, record: JournalRecord) {
+        match record {
+            JournalRecord::Add(path, time, entry) => {
+                self.index.entry(path).or_default().insert(time, entry);
+            }
+            JournalRecord::Quarantine(path, time) => {
+                let Some(versions) = self.index.get_mut(&path) else {
+                    return;
+                };
+                let Some(entry) = versions.remove(&time) else {
+                    return;
+                };
+
+                if versions.is_empty() {
+                    self.index.remove(&path);
+                }
+                self.quarantine
+                    .entry(path.clone())
+                    .or_default()
+                    .insert(time, entry);
+                self.forget(path, time);
+            }
+            JournalRecord::Remove(path, time) => {
+                let Some(versions) = self.index.get_mut(&path) else {
+                    return;
+                };
+
+                versions.remove(&time);
+                if versions.is_empty() {
+                    self.index.remove(&path);
+                }
+                self.forget(path, time);
+            }
+            JournalRecord::Pin(path, time, true) => {
+                if self.index.get(&path).is_some_and(|v| v.contains_key(&time)) {
+                    self.pinned.insert((path, time));
+                }
+            }
+            JournalRecord::Pin(path, time, false) => {
+                self.pinned.remove(&(path, time));
+            }
+            JournalRecord::Annotate(path, time, annotation) if annotation.is_empty() => {
+                self.annotations.remove(&(path, time));
+            }
+            JournalRecord::Annotate(path, time, annotation) => {
+                if self.index.get(&path).is_some_and(|v| v.contains_key(&time)) {
+                    self.annotations.insert((path, time), annotation);
+                }
+            }
+            JournalRecord::UndoPoint(path, Some(time)) => {
+                if self.index.get(&path).is_some_and(|v| v.contains_key(&time)) {
+                    self.undo_points.insert(path, time);
+                }
+            }
+            JournalRecord::UndoPoint(path, None) => {
+                self.undo_points.remove(&path);
+            }
+            JournalRecord::Describe(path, time, metadata) => {
+                if self.index.get(&path).is_some_and(|v| v.contains_key(&time)) {
+                    self.metadata.insert((path, time), metadata);
+                }
+            }
+        }
+    }
+
+    /// Drops what was kept about a version that left the history.
+    fn forget(&mut self, path: PathBuf, time: SystemTime) {
+        if self.undo_points.get(&path) == Some(&time) {
+            self.undo_points.remove(&path);
+        }
+
+        let key = (path, time);
+        self.pinned.remove(&key);
+        self.annotations.remove(&key);
+        self.metadata.remove(&key);
+    }
+
+    /// Takes a version out of the history. Its data is kept, so a quarantined version is never
+    /// lost for good.
+    pub fn quarantine(&mut self, path: PathBuf, time: SystemTime) -> Result<(), std::io::Error> {
+        let record = JournalRecord::Quarantine(path, time);
+        self.journal.append(&record)?;
+        self.apply_record(record);
+
+        Ok(())
+    }
+
+    pub fn set_pinned(
+        &mut self,
+        path: PathBuf,
+        time: SystemTime,
+        pinned: bool,
+    ) -> Result<(), std::io::Error> {
+        let record = JournalRecord::Pin(path, time, pinned);
+        self.journal.append(&record)?;
+        self.apply_record(record);
+
+        Ok(())
+    }
+
+    pub fn is_pinned(&self, path: &Path, time: &SystemTime) -> bool {
+        self.pinned.contains(&(path.to_path_buf(), *time))
+    }
+
+    pub fn annotate(
+        &mut self,
+        path: PathBuf,
+        time: SystemTime,
+        annotation: Annotation,
+    ) -> Result<(), std::io::Error> {
+        let record = JournalRecord::Annotate(path, time, annotation);
+        self.journal.append(&record)?;
+        self.apply_record(record);
+
+        Ok(())
+    }
+
+    pub fn annotation_of(&self, path: &Path, time: &SystemTime) -> Option<&Annotation> {
+        self.annotations.get(&(path.to_path_buf(), *time))
+    }
+
+    pub fn metadata_of(&self, path: &Path, time: &SystemTime) -> Option<&SaveMetadata> {
+        self.metadata.get(&(path.to_path_buf(), *time))
+    }
+
+    /// Records metadata read after the version was captured.
+    pub fn describe(
+        &mut self,
+        path: PathBuf,
+        time: SystemTime,
+        metadata: SaveMetadata,
+    ) -> Result<(), std::io::Error> {
+        let record = JournalRecord::Describe(path, time, metadata);
+        self.journal.append(&record)?;
+        self.apply_record(record);
+
+        Ok(())
+    }
+
+    /// Versions without metadata, which were captured before it was recorded.
+    pub fn undescribed(&self) -> Vec<(VersionKey, VersionEntry)> {
+        self.index
+            .iter()
+            .flat_map(|(path, versions)| {
+                versions
+                    .iter()
+                    .map(|(time, entry)| ((path.clone(), *time), *entry))
+            })
+            .filter(|(key, _)| !self.metadata.contains_key(key))
+            .collect()
+    }
+
+    /// Remembers the version to put the file back to should its last restore be undone.
+    pub fn set_undo_point(
+        &mut self,
+        path: PathBuf,
+        time: Option<SystemTime>,
+    ) -> Result<(), std::io::Error> {
+        let record = JournalRecord::UndoPoint(path, time);
+        self.journal.append(&record)?;
+        self.apply_record(record);
+
+        Ok(())
+    }
+
+    pub fn undo_point_of(&self, path: &Path) -> Option<SystemTime> {
+        self.undo_points.get(path).copied()
+    }
+
+    /// Versions the user may still need, which pruning leaves alone.
+    fn is_protected(&self, (path, time): &VersionKey) -> bool {
+        self.pinned.contains(&(path.clone(), *time)) || self.undo_points.get(path) == Some(time)
+    }
+
+    /// Removes the versions the policy no longer keeps and, while the stored data is over the
+    /// disk budget, the oldest of the rest but the newest of each file. Pinned versions and the
+    /// ones a restore can be undone to are never removed. Data no remaining version refers to is
+    /// deleted afterwards. Returns the number of versions removed.
+    pub fn prune(&mut self, policy: &RetentionPolicy) -> Result<usize, std::io::Error> {
+        let now = SystemTime::now();
+        let mut removals = self
+            .index
+            .iter()
+            .flat_map(|(path, versions)| {
+                policy
+                    .expired(versions.keys().copied(), now)
+                    .into_iter()
+                    .map(move |time| (path.clone(), time))
+            })
+            .filter(|key| !self.is_protected(key))
+            .collect::<HashSet<VersionKey>>();
+
+        if removals.is_empty() && policy.disk_budget.is_none() {
+            return Ok(0);
+        }
+
+        let mut usage = self.usage()?;
+        for (path, time) in &removals {
+            usage.release(self.index[path][time].hash());
+        }
+
+        if let Some(disk_budget) = policy.disk_budget {
+            let mut candidates = self
+                .index
+                .iter()
+                .flat_map(|(path, versions)| {
+                    let newest = versions.keys().max().copied();
+                    versions
+                        .iter()
+                        .filter(move |(time, _)| Some(**time) != newest)
+                        .map(move |(time, entry)| ((path.clone(), *time), *entry))
+                })
+                .filter(|(key, _)| !removals.contains(key) && !self.is_protected(key))
+                .collect::<Vec<(VersionKey, VersionEntry)>>();
+            candidates.sort_unstable_by_key(|((_, time), _)| *time);
+
+            for (key, entry) in candidates {
+                if usage.total() <= disk_budget {
+                    break;
+                }
+
+                usage.release(entry.hash());
+                removals.insert(key);
+            }
+        }
+
+        self.remove_and_reclaim(removals, &usage)
+    }
+
+    /// Removes the given versions but the pinned ones and deletes the data no remaining version
+    /// refers to. Returns the number of versions removed.
+    pub fn delete(&mut self, versions: Vec<VersionKey>) -> Result<usize, std::io::Error> {
+        let removals = versions
+            .into_iter()
+            .filter(|key| !self.pinned.contains(key))
+            .filter(|(path, time)| self.index.get(path).is_some_and(|v| v.contains_key(time)))
+            .collect::<HashSet<VersionKey>>();
+
+        if removals.is_empty() {
+            return Ok(0);
+        }
+
+        let mut usage = self.usage()?;
+        for (path, time) in &removals {
+            usage.release(self.index[path][time].hash());
+        }
+
+        self.remove_and_reclaim(removals, &usage)
+    }
+
+    /// Quarantined versions count as in use, since their data is kept.
+    fn usage(&self) -> Result<Usage, std::io::Error> {
+        self.versions.usage(
+            self.index
+                .values()
+                .chain(self.quarantine.values())
+                .flat_map(HashMap::values),
+        )
+    }
+
+    fn remove_and_reclaim(
+        &mut self,
+        removals: HashSet<VersionKey>,
+        usage: &Usage,
+    ) -> Result<usize, std::io::Error> {
+        let count = removals.len();
+        for (path, time) in removals {
+            self.remove(path, time)?;
+        }
+
+        self.versions.reclaim(usage)?;
+
+        Ok(count)
+    }
+
+    fn remove(&mut self, path: PathBuf, time: SystemTime) -> Result<(), std::io::Error> {
+        let record = JournalRecord::Remove(path, time);
+        self.journal.append(&record)?;
+        self.apply_record(record);
+
+        Ok(())
+    }
+
+    pub fn data_of(&self, path: &PathBuf, time: &SystemTime) -> Result<Vec<u8>, std::io::Error> {
+        let entry = self
+            .index
+            .get(path)
+            .and_then(|versions| versions.get(time))
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?;
+
+        self.versions.read(entry)
+    }
+
+    pub fn take_recovery(&mut self) -> Option<StorageRecovery> {
+        self.recovery.take()
+    }
+
+    /// Starts a background compaction once the journal has grown long enough and the previous
+    /// one has finished.
+    pub fn compact_if_needed(&mut self) -> Result<(), std::io::Error> {
+        if self.journal.len() < COMPACTION_THRESHOLD {
+            return Ok(());
+        }
+
+        if self.compaction.as_ref().is_some_and(|c| !c.is_finished()) {
+            return Ok(());
+        }
+
+        self.finish_compaction()?;
+        self.compact()
+    }
+
+    /// Moves on to a new journal generation and writes a checkpoint covering everything before
+    /// it on a separate thread.
+    fn compact(&mut self) -> Result<(), std::io::Error> {
+        let checkpoint = Checkpoint {
+            journal_generation: self.journal.rotate()?,
+            index: self.index.clone(),
+            quarantine: self.quarantine.clone(),
+            pinned: self.pinned.clone(),
+            annotations: self.annotations.clone(),
+            undo_points: self.undo_points.clone(),
+            metadata: self.metadata.clone(),
+        };
+        let previous_generation = self.checkpoint_generation;
+
+        self.compaction = Some(BackgroundTask::start(move || {
+            checkpoint.write()?;
+
+            // The replaced checkpoint is kept as backup, so the journals it needs are kept too.
+            Journal::remove_before(previous_generation)?;
+
+            Ok(checkpoint.journal_generation)
+        }));
+
+        Ok(())
+    }
+
+    fn finish_compaction(&mut self) -> Result<(), std::io::Error> {
+        let Some(compaction) = self.compaction.take() else {
+            return Ok(());
+        };
+
+        self.checkpoint_generation = compaction.join()?;
+
+        Ok(())
     }
 }
 
@@ -56,6 +530,12 @@ impl Deref for Storage {
     type Target = InnerType;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.index
+    }
+}
+
+impl Drop for Storage {
+    fn drop(&mut self) {
+        let _ = self.finish_compaction();
     }
 }
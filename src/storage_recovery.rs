@@ -0,0 +1,26 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum StorageRecovery {
+    FromBackup,
+    FromSaveFiles,
+    FromDamagedJournal,
+    LegacyUnreadable,
+}
+
+impl Display for StorageRecovery {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            Self::FromBackup => "Version index was damaged, restored its previous generation",
+            Self::FromSaveFiles => "Version index was damaged, rebuilt it from the save files",
+            Self::FromDamagedJournal => {
+                "Version journal was damaged, its unreadable changes were dropped and a copy of it was kept"
+            }
+            Self::LegacyUnreadable => {
+                "Version history of an earlier release could not be read, it was left in place and the save files were captured instead"
+            }
+        };
+
+        write!(f, "{message}")
+    }
+}
@@ -20,6 +20,16 @@ pub mod table {
     }
 }
 
+pub mod status {
+    use ratatui::style::Color;
+
+    use crate::ui::color_set::ColorSet;
+
+    pub const INFO: ColorSet = ColorSet::new(Color::White, Color::Black);
+    pub const WARNING: ColorSet = ColorSet::new(Color::Yellow, Color::Black);
+    pub const ERROR: ColorSet = ColorSet::new(Color::LightRed, Color::Black);
+}
+
 pub const HEADER: ColorSet = ColorSet::new(Color::White, Color::Black);
 pub const FOOTER: ColorSet = ColorSet::new(Color::White, Color::Black);
 pub const TABLE: ColorSet = ColorSet::new(Color::White, Color::Black);
@@ -1,5 +1,6 @@
+use std::collections::HashSet;
 use std::path::PathBuf;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use chrono::{DateTime, Local};
 use crossterm::{
@@ -13,20 +14,62 @@ use ratatui::{
     Terminal, widgets::{Block, Borders, TableState},
 };
 
+use ratatui::prelude::Line;
+
 use crate::context::Context;
+use crate::gamestate_browser::GamestateBrowser;
+use crate::logger::LogEntry;
 use crate::save_file::SaveFile;
+use crate::save_format::SaveMetadata;
 use crate::save_version::SaveVersion;
 use crate::state::State;
+use crate::ui::notification::{Notification, NotificationLevel};
 use crate::ui::table::draw;
+use crate::version_entry::VersionKey;
 
 mod color;
 mod color_set;
+pub mod notification;
 mod popup;
 mod style;
 mod table;
 
+const WIDTHS: [Constraint; 3] = [
+    Constraint::Length(4),
+    Constraint::Min(15),
+    Constraint::Min(15),
+];
+
+const NOTIFICATION_WIDTHS: [Constraint; 3] = [
+    Constraint::Length(8),
+    Constraint::Length(20),
+    Constraint::Min(15),
+];
+
+const LOG_WIDTHS: [Constraint; 3] = [
+    Constraint::Length(8),
+    Constraint::Length(24),
+    Constraint::Min(15),
+];
+
+const GAMESTATE_WIDTHS: [Constraint; 2] = [Constraint::Min(30), Constraint::Min(15)];
+
+const STATUS_DURATION: Duration = Duration::from_secs(5);
+
+const VERSION_WIDTHS: [Constraint; 9] = [
+    Constraint::Length(5),
+    Constraint::Length(19),
+    Constraint::Length(9),
+    Constraint::Length(10),
+    Constraint::Min(15),
+    Constraint::Length(8),
+    Constraint::Length(7),
+    Constraint::Length(6),
+    Constraint::Min(15),
+];
+
 pub fn run() -> Result<(), std::io::Error> {
-    let mut context = Context::new();
+    let mut context = Context::new()?;
 
     enable_raw_mode()?;
     std::io::stdout().execute(EnterAlternateScreen)?;
@@ -55,6 +98,7 @@ fn ui(frame: &mut Frame, context: &mut Context) -> Option<()> {
             Constraint::Length(1),
             Constraint::Min(0),
             Constraint::Length(1),
+            Constraint::Length(1),
         ],
     )
     .split(frame.size());
@@ -62,7 +106,7 @@ fn ui(frame: &mut Frame, context: &mut Context) -> Option<()> {
     render_header(frame, context, main_layout[0]);
 
     match context.state {
-        State::MainMenu(index, show_exit_confirmation) => {
+        State::MainMenu(index, _) | State::DeleteFile(index, _) => {
             context.table_state.select(Some(index));
             let save_files = context.save_storage.save_files();
             inflate_save_files(
@@ -73,11 +117,18 @@ fn ui(frame: &mut Frame, context: &mut Context) -> Option<()> {
                 &mut context.table_state,
             );
 
-            if show_exit_confirmation {
-                popup::show_exit_confirmation(frame);
+            match context.state {
+                State::MainMenu(_, true) => popup::show_exit_confirmation(frame),
+                State::DeleteFile(..) => popup::show_delete_file_confirmation(frame),
+                _ => {}
             }
         }
-        State::SaveFileSelected(index, main_menu_index, show_apply_confirmation) => {
+        State::SaveFileSelected(index, main_menu_index)
+        | State::Restore(index, main_menu_index, _)
+        | State::DeleteVersion(index, main_menu_index, _)
+        | State::EditLabel(index, main_menu_index, _)
+        | State::EditNote(index, main_menu_index, _)
+        | State::RestoreAs(index, main_menu_index, _) => {
             context.table_state.select(Some(index));
             let save_file = context.save_storage.save_files().nth(main_menu_index)?;
             let save_path = save_file.path();
@@ -86,25 +137,86 @@ fn ui(frame: &mut Frame, context: &mut Context) -> Option<()> {
                 frame,
                 main_layout[1],
                 save_versions,
+                &context.selection,
                 index,
                 &mut context.table_state,
             );
 
-            if show_apply_confirmation {
-                popup::show_apply_confirmation(frame);
+            match context.state {
+                State::Restore(..) => popup::show_apply_confirmation(frame),
+                State::DeleteVersion(_, _, ref versions) => {
+                    popup::show_delete_version_confirmation(frame, versions.len());
+                }
+                State::EditLabel(..) => popup::show_text_input(frame, "Label", &context.text_input),
+                State::EditNote(..) => popup::show_text_input(frame, "Note", &context.text_input),
+                State::RestoreAs(..) => {
+                    popup::show_text_input(frame, "Restore as", &context.text_input);
+                }
+                _ => {}
+            }
+        }
+        State::CorruptedVersions(index) => {
+            context.table_state.select(Some(index));
+            inflate_corrupted_versions(
+                frame,
+                main_layout[1],
+                &context.corrupted_versions,
+                index,
+                &mut context.table_state,
+            );
+        }
+        State::Notifications(index) => {
+            context.table_state.select(Some(index));
+            inflate_notifications(
+                frame,
+                main_layout[1],
+                &context.notifications,
+                index,
+                &mut context.table_state,
+            );
+        }
+        State::Logs(index) => {
+            context.table_state.select(Some(index));
+            inflate_log_entries(
+                frame,
+                main_layout[1],
+                &context.log_entries,
+                index,
+                &mut context.table_state,
+            );
+        }
+        State::Gamestate(cursor, ..) => {
+            if let Some(gamestate_browser) = &mut context.gamestate_browser {
+                inflate_gamestate(
+                    frame,
+                    main_layout[1],
+                    gamestate_browser,
+                    cursor,
+                    &mut context.table_state,
+                );
             }
         }
         State::Exit => {}
     }
 
-    render_footer(frame, &context.state, main_layout[2]);
+    if let Some(notice) = &context.notice {
+        popup::show_notice(frame, notice);
+    }
+
+    render_status_line(frame, &context.notifications, main_layout[2]);
+    render_footer(frame, context, main_layout[3]);
 
     None
 }
 
 fn render_header(frame: &mut Frame, context: &Context, area: Rect) {
     let subtitle = match context.state {
-        State::SaveFileSelected(_, main_menu_index, _) => {
+        State::SaveFileSelected(_, main_menu_index)
+        | State::Restore(_, main_menu_index, _)
+        | State::DeleteVersion(_, main_menu_index, _)
+        | State::EditLabel(_, main_menu_index, _)
+        | State::EditNote(_, main_menu_index, _)
+        | State::RestoreAs(_, main_menu_index, _) => {
             let save_file = context
                 .save_storage
                 .save_files()
@@ -113,6 +225,13 @@ fn render_header(frame: &mut Frame, context: &Context, area: Rect) {
             let file_name = save_file.path().file_name().unwrap_or_default();
             format!(" - {}", file_name.to_string_lossy())
         }
+        State::CorruptedVersions(_) => " - Corrupted versions".to_owned(),
+        State::Logs(_) => format!(" - Log ({} and above)", context.log_filter),
+        State::Gamestate(..) if context.is_loading_gamestate() => {
+            " - Loading gamestate...".to_owned()
+        }
+        State::Gamestate(..) => " - Gamestate".to_owned(),
+        _ if context.save_storage.is_verifying() => " - Verifying...".to_owned(),
         _ => String::new(),
     };
 
@@ -143,45 +262,292 @@ fn inflate_save_files(
         Some([order, file_name, time_string].into_iter())
     });
 
-    draw(frame, rect, header.into_iter(), rows, selected, table_state);
+    draw(
+        frame,
+        rect,
+        header.into_iter(),
+        &WIDTHS,
+        rows,
+        selected,
+        table_state,
+    );
 }
 
-fn inflate_save_versions(
+fn inflate_save_versions<'a>(
     frame: &mut Frame,
     rect: Rect,
-    save_versions: impl Iterator<Item = SaveVersion>,
+    save_versions: impl Iterator<Item = SaveVersion<'a>>,
+    marked: &HashSet<SystemTime>,
     selected: usize,
     table_state: &mut TableState,
 ) {
-    let header = ["#", "Last Modified"];
+    let header = [
+        "#",
+        "Last Modified",
+        "Size",
+        "Date",
+        "Ruler",
+        "Game",
+        "Ironman",
+        "Pinned",
+        "Label",
+    ];
 
     let rows = save_versions.enumerate().map(|(order, version)| {
-        let order = format!("{order}");
+        let mark = if marked.contains(version.time()) {
+            "*"
+        } else {
+            " "
+        };
+        let order = format!("{mark}{order}");
         let time = version.time();
         let time = DateTime::<Local>::from(*time);
         let time_string = time.format("%d/%m/%Y %T").to_string();
+        let size = format_size(version.size());
+        let metadata = version.metadata();
+        let date = metadata
+            .and_then(|metadata| metadata.date)
+            .map(|d| d.to_string())
+            .unwrap_or_default();
+        let ruler = metadata.and_then(SaveMetadata::ruler).unwrap_or_default();
+        let game_version = metadata
+            .and_then(|metadata| metadata.game_version.clone())
+            .unwrap_or_default();
+        let ironman = if metadata.is_some_and(|metadata| metadata.is_ironman) {
+            "Yes"
+        } else {
+            ""
+        }
+        .to_owned();
+        let pinned = if version.is_pinned() { "Yes" } else { "" }.to_owned();
+        let label = version.label().to_owned();
+
+        [
+            order,
+            time_string,
+            size,
+            date,
+            ruler,
+            game_version,
+            ironman,
+            pinned,
+            label,
+        ]
+        .into_iter()
+    });
+
+    draw(
+        frame,
+        rect,
+        header.into_iter(),
+        &VERSION_WIDTHS,
+        rows,
+        selected,
+        table_state,
+    );
+}
+
+fn inflate_corrupted_versions(
+    frame: &mut Frame,
+    rect: Rect,
+    corrupted_versions: &[VersionKey],
+    selected: usize,
+    table_state: &mut TableState,
+) {
+    let header = ["#", "Filename", "Last Modified"];
+
+    let rows = corrupted_versions
+        .iter()
+        .enumerate()
+        .filter_map(|(order, (path, time))| {
+            let order = format!("{order}");
+            let file_name = path.file_name()?.to_string_lossy().to_string();
+            let time = DateTime::<Local>::from(*time);
+            let time_string = time.format("%d/%m/%Y %T").to_string();
+
+            Some([order, file_name, time_string].into_iter())
+        });
+
+    draw(
+        frame,
+        rect,
+        header.into_iter(),
+        &WIDTHS,
+        rows,
+        selected,
+        table_state,
+    );
+}
+
+/// Newest first.
+fn inflate_notifications(
+    frame: &mut Frame,
+    rect: Rect,
+    notifications: &[Notification],
+    selected: usize,
+    table_state: &mut TableState,
+) {
+    let header = ["Level", "Time", "Message"];
+
+    let rows = notifications.iter().rev().map(|notification| {
+        let level = notification.level().to_string();
+        let time = DateTime::<Local>::from(*notification.time());
+        let time_string = time.format("%d/%m/%Y %T").to_string();
+        let message = notification.message().to_owned();
+
+        [level, time_string, message].into_iter()
+    });
+
+    draw(
+        frame,
+        rect,
+        header.into_iter(),
+        &NOTIFICATION_WIDTHS,
+        rows,
+        selected,
+        table_state,
+    );
+}
+
+/// Newest first, like the notifications.
+fn inflate_log_entries(
+    frame: &mut Frame,
+    rect: Rect,
+    log_entries: &[LogEntry],
+    selected: usize,
+    table_state: &mut TableState,
+) {
+    let header = ["Level", "Time", "Message"];
+
+    let rows = log_entries.iter().rev().map(|entry| {
+        let level = entry.level().to_string();
+        let time = entry.time().to_owned();
+        let message = entry.message().to_owned();
 
-        [order, time_string].into_iter()
+        [level, time, message].into_iter()
     });
 
-    draw(frame, rect, header.into_iter(), rows, selected, table_state);
+    draw(
+        frame,
+        rect,
+        header.into_iter(),
+        &LOG_WIDTHS,
+        rows,
+        selected,
+        table_state,
+    );
+}
+
+/// Only the rows that fit on screen are drawn, the browser keeps track of where they start.
+fn inflate_gamestate(
+    frame: &mut Frame,
+    rect: Rect,
+    gamestate_browser: &mut GamestateBrowser,
+    cursor: usize,
+    table_state: &mut TableState,
+) {
+    let header = ["Key", "Value"];
+
+    let height = rect.height.saturating_sub(1) as usize;
+    let (rows, selected) = gamestate_browser.window(cursor, height);
+    table_state.select(Some(selected));
+    *table_state.offset_mut() = 0;
+
+    draw(
+        frame,
+        rect,
+        header.into_iter(),
+        &GAMESTATE_WIDTHS,
+        rows.into_iter().map(IntoIterator::into_iter),
+        selected,
+        table_state,
+    );
+}
+
+/// Shows the latest notification for a few seconds after it was pushed.
+fn render_status_line(frame: &mut Frame, notifications: &[Notification], area: Rect) {
+    let Some(notification) = notifications.last() else {
+        return;
+    };
+
+    let age = notification.time().elapsed().unwrap_or_default();
+    if age > STATUS_DURATION {
+        return;
+    }
+
+    let style = match notification.level() {
+        NotificationLevel::Info => style::status::INFO,
+        NotificationLevel::Warning => style::status::WARNING,
+        NotificationLevel::Error => style::status::ERROR,
+    };
+    let text = format!(" {}: {}", notification.level(), notification.message());
+
+    frame.render_widget(Line::from(text).style(style), area);
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
 }
 
-fn render_footer(frame: &mut Frame, state: &State, area: Rect) {
-    let title = match state {
+fn render_footer(frame: &mut Frame, context: &Context, area: Rect) {
+    let title = match context.state {
         State::MainMenu(_, false) => {
-            "[↑] Cursor Up [↓] Cursor Down [ESC] Exit [ENTER] See version history"
+            "[↑] Cursor Up [↓] Cursor Down [ESC] Exit [ENTER] See version history [D] Delete history [V] Verify versions [M] Messages [L] Log"
         }
         State::MainMenu(_, true) => "[ESC] Go back [ENTER] Exit program",
-        State::SaveFileSelected(_, _, false) => {
-            "[↑] Cursor Up [↓] Cursor Down [ESC] Go back to file list [ENTER] Revert to version"
+        State::SaveFileSelected(_, _) => {
+            "[↑] Cursor Up [↓] Cursor Down [ESC] Go back to file list [ENTER] Revert to version [A] Restore as [SPACE] Select [D] Delete [P] Pin/Unpin [E] Export [L] Label [N] Note [B] Browse gamestate"
+        }
+        State::Restore(..) => "[ESC] Cancel revert [ENTER] Apply version",
+        State::DeleteFile(..) | State::DeleteVersion(..) => "[ESC] Cancel delete [ENTER] Delete",
+        State::EditLabel(..) | State::EditNote(..) => "[ESC] Cancel [ENTER] Save",
+        State::RestoreAs(..) => "[ESC] Cancel [ENTER] Restore to new file",
+        State::CorruptedVersions(_) => {
+            "[↑] Cursor Up [↓] Cursor Down [ESC] Keep and go back [ENTER] Quarantine all"
+        }
+        State::Notifications(_) => "[↑] Cursor Up [↓] Cursor Down [ESC] Go back to file list",
+        State::Logs(_) => {
+            "[↑] Cursor Up [↓] Cursor Down [ESC] Go back to file list [F] Change level filter"
+        }
+        State::Gamestate(..) => {
+            "[↑] Cursor Up [↓] Cursor Down [PGUP] Page Up [PGDN] Page Down [ENTER] Expand/Collapse [→] Expand [←] Collapse [ESC] Go back to version list"
         }
-        State::SaveFileSelected(_, _, true) => "[ESC] Cancel revert [ENTER] Apply version",
         State::Exit => "",
     };
 
+    let can_undo_restore = match context.state {
+        State::MainMenu(main_menu_index, false) | State::SaveFileSelected(_, main_menu_index) => {
+            context
+                .save_storage
+                .save_files()
+                .nth(main_menu_index)
+                .is_some_and(|f| context.save_storage.can_undo_restore(f.path()))
+        }
+        _ => false,
+    };
+    let undo_title = if can_undo_restore {
+        " [U] Undo restore"
+    } else {
+        ""
+    };
+    let corrupted_title = match context.state {
+        State::MainMenu(_, false) if !context.corrupted_versions.is_empty() => {
+            " [C] Corrupted versions"
+        }
+        _ => "",
+    };
+
     let footer = Block::new()
-        .title(format!(" {title} "))
+        .title(format!(" {title}{undo_title}{corrupted_title} "))
         .style(style::FOOTER)
         .borders(Borders::TOP);
 
@@ -0,0 +1,74 @@
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use log::{Level, log};
+
+static PENDING: Mutex<Vec<Notification>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum NotificationLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Display for NotificationLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Info => "Info",
+            Self::Warning => "Warning",
+            Self::Error => "Error",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+pub struct Notification {
+    level: NotificationLevel,
+    message: String,
+    time: SystemTime,
+}
+
+impl Notification {
+    pub const fn level(&self) -> NotificationLevel {
+        self.level
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub const fn time(&self) -> &SystemTime {
+        &self.time
+    }
+}
+
+/// Can be called from any thread. The message shows up on the next update of the UI and is
+/// written to the log as well.
+pub fn push(level: NotificationLevel, message: impl Into<String>) {
+    let message = message.into();
+
+    let log_level = match level {
+        NotificationLevel::Info => Level::Info,
+        NotificationLevel::Warning => Level::Warn,
+        NotificationLevel::Error => Level::Error,
+    };
+    log!(log_level, "{message}");
+
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.push(Notification {
+            level,
+            message,
+            time: SystemTime::now(),
+        });
+    }
+}
+
+pub fn take_pending() -> Vec<Notification> {
+    PENDING
+        .lock()
+        .map(|mut pending| std::mem::take(&mut *pending))
+        .unwrap_or_default()
+}
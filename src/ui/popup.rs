@@ -1,6 +1,6 @@
 use ratatui::Frame;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
-use ratatui::prelude::Line;
+use ratatui::prelude::{Line, Text};
 use ratatui::widgets::Block;
 
 pub fn show_exit_confirmation(frame: &mut Frame) {
@@ -21,6 +21,93 @@ pub fn show_apply_confirmation(frame: &mut Frame) {
     );
 }
 
+pub fn show_delete_file_confirmation(frame: &mut Frame) {
+    show_esc_enter_popup(
+        frame,
+        "Are you sure you want to delete the history of this file?",
+        "Go back",
+        "Confirm delete",
+    );
+}
+
+pub fn show_delete_version_confirmation(frame: &mut Frame, count: usize) {
+    let question = if count > 1 {
+        format!("Are you sure you want to delete {count} versions?")
+    } else {
+        "Are you sure you want to delete this version?".to_owned()
+    };
+
+    show_esc_enter_popup(frame, &question, "Go back", "Confirm delete");
+}
+
+/// Messages wider than the terminal are wrapped onto more lines.
+pub fn show_notice(frame: &mut Frame, message: &str) {
+    let rect = frame.size();
+    let accept_line = Line::from("[ENTER] OK").centered();
+    let line_width = u16::try_from(Line::from(message).width().max(accept_line.width()) + 4)
+        .unwrap_or(u16::MAX)
+        .min(rect.width);
+    let message_lines = wrap_words(message, usize::from(line_width.saturating_sub(4)));
+    let message_height = u16::try_from(message_lines.len()).unwrap_or(u16::MAX);
+    let popup_height = message_height.saturating_add(4);
+
+    let area = centered_rect(line_width, popup_height, rect);
+
+    let block = Block::bordered();
+    let block_area = block.inner(area);
+
+    let inner_layout = Layout::new(
+        Direction::Vertical,
+        [
+            Constraint::Length(message_height),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ],
+    )
+    .split(block_area);
+
+    let message_lines = message_lines
+        .into_iter()
+        .map(Line::from)
+        .collect::<Vec<Line>>();
+    frame.render_widget(Text::from(message_lines).centered(), inner_layout[0]);
+    frame.render_widget(accept_line, inner_layout[2]);
+
+    frame.render_widget(block, area);
+}
+
+/// Shows the end of the text being edited, so the part being typed stays in view.
+pub fn show_text_input(frame: &mut Frame, title: &str, text: &str) {
+    let rect = frame.size();
+    let line_width = 60.min(rect.width);
+    let visible_length = usize::from(line_width.saturating_sub(5));
+
+    let skipped = text.chars().count().saturating_sub(visible_length);
+    let text_line = Line::from(text.chars().skip(skipped).collect::<String>() + "_");
+    let keys_line = Line::from("[ESC] Cancel [ENTER] Save").centered();
+    let popup_height = 5;
+
+    let area = centered_rect(line_width, popup_height, rect);
+
+    let block = Block::bordered().title(format!(" {title} "));
+    let block_area = block.inner(area);
+
+    let inner_layout = Layout::new(
+        Direction::Vertical,
+        [
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ],
+    )
+    .split(block_area);
+
+    frame.render_widget(text_line, inner_layout[0]);
+    frame.render_widget(keys_line, inner_layout[2]);
+
+    frame.render_widget(block, area);
+}
+
 fn show_esc_enter_popup(
     frame: &mut Frame,
     question_message: &str,
@@ -68,8 +155,28 @@ fn show_esc_enter_popup(
     frame.render_widget(block, area);
 }
 
+/// Breaks `text` at spaces into lines of at most `width` characters. A longer word gets a line of
+/// its own and is cut off there.
+fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+
+    lines
+}
+
+/// Popups larger than `r` are shrunk to fit it.
 fn centered_rect(width: u16, height: u16, r: Rect) -> Rect {
-    let vertical_offset = (r.height - height) / 2;
+    let vertical_offset = r.height.saturating_sub(height) / 2;
     let popup_layout = Layout::vertical([
         Constraint::Length(vertical_offset),
         Constraint::Length(height),
@@ -77,7 +184,7 @@ fn centered_rect(width: u16, height: u16, r: Rect) -> Rect {
     ])
     .split(r);
 
-    let horizontal_offset = (r.width - width) / 2;
+    let horizontal_offset = r.width.saturating_sub(width) / 2;
     Layout::horizontal([
         Constraint::Length(horizontal_offset),
         Constraint::Length(width),
@@ -34,3 +34,19 @@ pub mod table {
             .bg(color::table::selected::ODD.bg());
     }
 }
+
+pub mod status {
+    use ratatui::prelude::Style;
+
+    use crate::ui::style::color;
+
+    pub const INFO: Style = Style::new()
+        .fg(color::status::INFO.fg())
+        .bg(color::status::INFO.bg());
+    pub const WARNING: Style = Style::new()
+        .fg(color::status::WARNING.fg())
+        .bg(color::status::WARNING.bg());
+    pub const ERROR: Style = Style::new()
+        .fg(color::status::ERROR.fg())
+        .bg(color::status::ERROR.bg());
+}
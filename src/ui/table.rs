@@ -9,6 +9,7 @@ pub fn draw<'a>(
     frame: &mut Frame,
     rect: Rect,
     header: impl Iterator<Item = &'a str>,
+    widths: &[Constraint],
     rows: impl Iterator<Item = impl Iterator<Item = String>>,
     selected: usize,
     table_state: &mut TableState,
@@ -23,24 +24,17 @@ pub fn draw<'a>(
     let rows = rows.enumerate().map(row_from_index_and_items);
 
     let select_bar = " █ ";
-    let table = Table::new(
-        rows,
-        [
-            Constraint::Length(4),
-            Constraint::Min(15),
-            Constraint::Min(15),
-        ],
-    )
-    .header(header)
-    .highlight_style(selected_style)
-    .highlight_symbol(Text::from(vec![
-        "".into(),
-        select_bar.into(),
-        select_bar.into(),
-        "".into(),
-    ]))
-    .style(style::TABLE)
-    .highlight_spacing(HighlightSpacing::Always);
+    let table = Table::new(rows, widths.iter().copied())
+        .header(header)
+        .highlight_style(selected_style)
+        .highlight_symbol(Text::from(vec![
+            "".into(),
+            select_bar.into(),
+            select_bar.into(),
+            "".into(),
+        ]))
+        .style(style::TABLE)
+        .highlight_spacing(HighlightSpacing::Always);
 
     frame.render_stateful_widget(table, rect, table_state);
 }
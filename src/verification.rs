@@ -0,0 +1,21 @@
+use crate::background_task::BackgroundTask;
+use crate::version_entry::{VersionEntry, VersionKey};
+use crate::version_store::VersionStore;
+
+pub type Verification = BackgroundTask<Vec<VersionKey>>;
+
+/// Reads every given version back on a separate thread and collects the ones that no longer match
+/// the hash recorded when they were captured.
+pub fn verify(versions: Vec<(VersionKey, VersionEntry)>) -> Verification {
+    BackgroundTask::start(move || {
+        let version_store = VersionStore::open()?;
+
+        let corrupted = versions
+            .into_iter()
+            .filter(|(_, entry)| version_store.read(entry).is_err())
+            .map(|(key, _)| key)
+            .collect();
+
+        Ok(corrupted)
+    })
+}
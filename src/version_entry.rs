@@ -0,0 +1,26 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::object_hash::ObjectHash;
+
+/// A version in the history, named by the file it was captured from and that file's modification
+/// time.
+pub type VersionKey = (PathBuf, SystemTime);
+
+/// What the index keeps in memory for a version. The bytes stay on disk until they are needed.
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct VersionEntry(ObjectHash, u64);
+
+impl VersionEntry {
+    pub const fn new(hash: ObjectHash, size: u64) -> Self {
+        Self(hash, size)
+    }
+
+    pub const fn hash(&self) -> &ObjectHash {
+        &self.0
+    }
+
+    pub const fn size(&self) -> u64 {
+        self.1
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use crate::chunk::chunks;
+use crate::codec::Codec;
+use crate::object_hash::ObjectHash;
+use crate::object_store::ObjectStore;
+use crate::path::{manifests_directory, objects_directory};
+use crate::version_entry::VersionEntry;
+
+type Manifest = Vec<ObjectHash>;
+
+/// Stores version bytes as deduplicated chunks, with a manifest listing the chunks of each
+/// distinct version under the hash of its whole contents.
+pub struct VersionStore {
+    objects: ObjectStore,
+    manifests: ObjectStore,
+}
+
+impl VersionStore {
+    pub fn open() -> Result<Self, std::io::Error> {
+        Ok(Self {
+            objects: ObjectStore::open(objects_directory()?, Codec::Zstd)?,
+            manifests: ObjectStore::open(manifests_directory()?, Codec::Raw)?,
+        })
+    }
+
+    pub fn insert(&self, data: &[u8]) -> Result<VersionEntry, std::io::Error> {
+        let hash = ObjectHash::of(data);
+        if !self.manifests.contains(&hash) {
+            let manifest = chunks(data)
+                .map(|chunk| self.objects.insert(chunk))
+                .collect::<Result<Manifest, std::io::Error>>()?;
+            let bytes = postcard::to_stdvec(&manifest)
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
+            self.manifests.write(&hash, &bytes)?;
+        }
+
+        Ok(VersionEntry::new(hash, data.len() as u64))
+    }
+
+    /// Reads the bytes of a version from disk, checking every chunk and the whole against the
+    /// hashes recorded when it was captured. Nothing is cached, so memory use does not grow with
+    /// the number of versions that were looked at.
+    pub fn read(&self, entry: &VersionEntry) -> Result<Vec<u8>, std::io::Error> {
+        let hash = entry.hash();
+        let manifest = self.manifest_of(hash)?;
+
+        let capacity = usize::try_from(entry.size()).map_err(|_| corrupted())?;
+        let mut data = Vec::with_capacity(capacity);
+        for chunk_hash in &manifest {
+            let chunk = self.objects.read(chunk_hash)?;
+            if ObjectHash::of(&chunk) != *chunk_hash {
+                return Err(corrupted());
+            }
+
+            data.extend(chunk);
+        }
+
+        if ObjectHash::of(&data) != *hash {
+            return Err(corrupted());
+        }
+
+        Ok(data)
+    }
+
+    /// Counts the references to the chunks of the given versions and their size on disk.
+    pub fn usage<'a>(
+        &self,
+        entries: impl Iterator<Item = &'a VersionEntry>,
+    ) -> Result<Usage, std::io::Error> {
+        let mut usage = Usage::default();
+        for entry in entries {
+            let hash = entry.hash();
+            let references = usage.versions.entry(*hash).or_default();
+            *references += 1;
+            if *references > 1 {
+                continue;
+            }
+
+            let manifest = self.manifest_of(hash)?;
+            for chunk_hash in &manifest {
+                let references = usage.chunks.entry(*chunk_hash).or_default();
+                *references += 1;
+                if *references == 1 {
+                    let size = self.objects.size_of(chunk_hash).unwrap_or_default();
+                    usage.sizes.insert(*chunk_hash, size);
+                    usage.total += size;
+                }
+            }
+            usage.manifests.insert(*hash, manifest);
+        }
+
+        Ok(usage)
+    }
+
+    /// Deletes the manifests and chunks no version refers to anymore.
+    pub fn reclaim(&self, usage: &Usage) -> Result<(), std::io::Error> {
+        for (hash, references) in &usage.versions {
+            if *references == 0 {
+                self.manifests.remove(hash)?;
+            }
+        }
+
+        for (hash, references) in &usage.chunks {
+            if *references == 0 {
+                self.objects.remove(hash)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Versions stored before chunking was introduced are kept whole under their own hash, which
+    /// reads as a manifest of a single chunk.
+    fn manifest_of(&self, hash: &ObjectHash) -> Result<Manifest, std::io::Error> {
+        match self.manifests.read(hash) {
+            Ok(bytes) => postcard::from_bytes(&bytes).map_err(|_| corrupted()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![*hash]),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Reference counts of the chunks behind a set of versions, telling how much disk space
+/// releasing some of them gives back.
+#[derive(Default)]
+pub struct Usage {
+    versions: HashMap<ObjectHash, usize>,
+    manifests: HashMap<ObjectHash, Manifest>,
+    chunks: HashMap<ObjectHash, usize>,
+    sizes: HashMap<ObjectHash, u64>,
+    total: u64,
+}
+
+impl Usage {
+    pub const fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Drops one reference to a version. Its chunks stop counting once no version refers to them.
+    pub fn release(&mut self, hash: &ObjectHash) {
+        let Some(references) = self.versions.get_mut(hash).filter(|r| **r > 0) else {
+            return;
+        };
+        *references -= 1;
+        if *references > 0 {
+            return;
+        }
+
+        for chunk_hash in self.manifests.get(hash).into_iter().flatten() {
+            let Some(references) = self.chunks.get_mut(chunk_hash) else {
+                continue;
+            };
+            *references -= 1;
+            if *references == 0 {
+                self.total -= self.sizes.get(chunk_hash).copied().unwrap_or_default();
+            }
+        }
+    }
+}
+
+fn corrupted() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "Version data does not match its checksum",
+    )
+}